@@ -0,0 +1,110 @@
+use pvs::{Items, SceneItem};
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+use crate::CliError;
+
+const INDENT: &str = "  ";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub assemblies: usize,
+    pub leaves: usize,
+    pub hidden_instances: usize,
+    pub max_depth: usize,
+    pub shape_files: BTreeSet<String>,
+}
+
+impl Summary {
+    fn add(&mut self, item: &SceneItem) {
+        match &item.source {
+            Some(s) => {
+                self.leaves += 1;
+                self.shape_files.insert(s.file_name.clone());
+            }
+            None => self.assemblies += 1,
+        }
+        self.max_depth = self.max_depth.max(item.depth);
+    }
+}
+
+// Prints each item as it streams in, followed by totals for the whole assembly.
+pub fn print_tree<W: Write>(mut w: W, mut items: Items) -> Result<Summary, CliError> {
+    let write_error = |e| CliError::Io("Error writing summary".to_string(), e);
+    let mut summary = Summary::default();
+    for item in &mut items {
+        let item = item?;
+        write_item(&mut w, &item).map_err(write_error)?;
+        summary.add(&item);
+    }
+    summary.hidden_instances = items.hidden_instances();
+    write_summary(&mut w, &summary).map_err(write_error)?;
+
+    Ok(summary)
+}
+
+fn write_item<W: Write>(w: &mut W, item: &SceneItem) -> io::Result<()> {
+    write!(
+        w,
+        "{}{} {}",
+        INDENT.repeat(item.depth),
+        item.supplied_id,
+        item.name
+    )?;
+    if let Some(s) = &item.source {
+        write!(w, " [{}]", s.file_name)?;
+    }
+    write!(w, " (depth {})", item.depth)?;
+    if !item.visible {
+        write!(w, " hidden")?;
+    }
+    writeln!(w)
+}
+
+fn write_summary<W: Write>(w: &mut W, summary: &Summary) -> io::Result<()> {
+    writeln!(w)?;
+    writeln!(w, "Assemblies: {}", summary.assemblies)?;
+    writeln!(w, "Leaves: {}", summary.leaves)?;
+    writeln!(w, "Hidden instances: {}", summary.hidden_instances)?;
+    writeln!(w, "Max depth: {}", summary.max_depth)?;
+    writeln!(w, "Unique shape files: {}", summary.shape_files.len())?;
+    for f in &summary.shape_files {
+        writeln!(w, "{}{}", INDENT, f)?;
+    }
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pvs::ConvertOptions;
+
+    #[test]
+    fn prints_tree_and_summary() {
+        let items = pvs::items("../pvs/tests/pvs.xml", &ConvertOptions::default()).unwrap();
+        let mut out = vec![];
+        let summary = print_tree(&mut out, items).unwrap();
+
+        assert_eq!(2, summary.assemblies);
+        assert_eq!(3, summary.leaves);
+        assert_eq!(1, summary.hidden_instances);
+        assert_eq!(2, summary.max_depth);
+        assert_eq!(
+            "/ TN1, Model (depth 0)
+  /109 TN1, Model (depth 1)
+    /109/104 PN1, Bolt [PN1.ol] (depth 2)
+    /109/107 PN0, Washer [PN0.ol] (depth 2)
+  /107 PN0, Washer [PN0.ol] (depth 1)
+
+Assemblies: 2
+Leaves: 3
+Hidden instances: 1
+Max depth: 2
+Unique shape files: 2
+  PN0.ol
+  PN1.ol
+",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
@@ -1,49 +1,411 @@
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use log::{info, LevelFilter};
+use pvs::{
+    ConvertOptions, HiddenInstances, LengthUnit, OrientationCheck, Precision, PropertyFilter,
+    PvsError, RootSelection,
+};
 use std::env;
+use std::ffi::OsString;
+use std::fmt;
 use std::fs::File;
-use std::io::BufWriter;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
 use std::process;
 
-pub struct Config {
-    pub src: String,
-    pub dst: String,
+mod inspect;
+
+const STDIO_PATH: &str = "-";
+const SUBCOMMANDS: &[&str] = &["convert", "inspect", "validate", "help"];
+
+const EXIT_IO: i32 = 3;
+const EXIT_XML: i32 = 4;
+const EXIT_INVALID_PVS: i32 = 5;
+
+/// Converts PVS assemblies into JSON scene items
+///
+/// `cli <SRC> <DST>` without a subcommand is the same as `cli convert <SRC> <DST>`.
+#[derive(Parser)]
+#[command(
+    name = "cli",
+    version,
+    after_help = "Exit codes: 0 success, 2 invalid arguments, 3 I/O error, 4 malformed XML, 5 invalid PVS"
+)]
+struct Cli {
+    /// Only print errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Print progress details, repeat for debug and trace output
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+    #[command(subcommand)]
+    command: Command,
 }
 
-impl Config {
-    pub fn new(mut args: env::Args) -> Result<Config, &'static str> {
-        args.next();
+impl Cli {
+    // RUST_LOG still overrides this for finer-grained filtering.
+    fn log_level(&self) -> LevelFilter {
+        if self.quiet {
+            return LevelFilter::Error;
+        }
+        match self.verbose {
+            0 => LevelFilter::Warn,
+            1 => LevelFilter::Info,
+            2 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        }
+    }
+}
 
-        let src = match args.next() {
-            Some(a) => a,
-            None => return Err("Must specify src path"),
+#[derive(Subcommand)]
+enum Command {
+    /// Convert a PVS file into JSON scene items
+    Convert(ConvertArgs),
+    /// Print a summary of the assembly in a PVS file
+    Inspect(SourceArgs),
+    /// Check that a PVS file converts without errors
+    Validate(SourceArgs),
+}
+
+#[derive(Args)]
+struct SourceArgs {
+    /// PVS file to read, or - for stdin
+    src: String,
+    /// Hidden instances: skip them with their subtree, keep them marked invisible, or prune only
+    /// what is hidden
+    #[arg(long, default_value = "skip")]
+    hidden: HiddenInstances,
+    /// Fail when the assembly is nested deeper than this
+    #[arg(long)]
+    max_depth: Option<usize>,
+    /// Non-orthonormal orientations: ignore, warn, error or orthonormalize
+    #[arg(long, default_value = "warn")]
+    orientations: OrientationCheck,
+    /// Accumulate transforms in single or double precision
+    #[arg(long, default_value = "single")]
+    precision: Precision,
+    /// Only include these properties in item metadata
+    #[arg(long, value_delimiter = ',', conflicts_with = "exclude_properties")]
+    properties: Vec<String>,
+    /// Exclude these properties from item metadata
+    #[arg(long, value_delimiter = ',')]
+    exclude_properties: Vec<String>,
+    /// Root component: detect, last, index:N, name:NAME, or all for one tree per root
+    #[arg(long, default_value = "detect")]
+    root: RootSelection,
+    /// Snap transform values within this distance of 0, 1 or -1
+    #[arg(long, value_name = "EPSILON")]
+    snap: Option<f32>,
+    /// Length unit of the PVS file: m, cm, mm, in or ft
+    #[arg(long, default_value = "m")]
+    source_units: LengthUnit,
+    /// Length unit of the scene items: m, cm, mm, in or ft
+    #[arg(long, default_value = "mm")]
+    units: LengthUnit,
+}
+
+impl SourceArgs {
+    fn options(&self) -> ConvertOptions {
+        let properties = if !self.properties.is_empty() {
+            PropertyFilter::Allow(self.properties.iter().cloned().collect())
+        } else if !self.exclude_properties.is_empty() {
+            PropertyFilter::Deny(self.exclude_properties.iter().cloned().collect())
+        } else {
+            PropertyFilter::All
         };
 
-        let dst = match args.next() {
-            Some(a) => a,
-            None => return Err("Must specify dst path"),
+        ConvertOptions {
+            hidden: self.hidden,
+            max_depth: self.max_depth,
+            orientations: self.orientations,
+            precision: self.precision,
+            properties,
+            root: self.root.clone(),
+            snap_epsilon: self.snap,
+            source_units: self.source_units,
+            target_units: self.units,
+        }
+    }
+
+    fn items(&self) -> Result<pvs::Items, CliError> {
+        let reader: Box<dyn BufRead> = if self.src == STDIO_PATH {
+            Box::new(io::stdin().lock())
+        } else {
+            Box::new(BufReader::new(File::open(&self.src).map_err(|e| {
+                CliError::Io(format!("Error opening {}", self.src), e)
+            })?))
         };
 
-        Ok(Config { src, dst })
+        Ok(pvs::items_from_reader(reader, &self.options())?)
+    }
+}
+
+#[derive(Args)]
+struct ConvertArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+    /// JSON file to write, or - for stdout
+    dst: String,
+    /// Output layout
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum Format {
+    /// A single JSON array
+    Json,
+    /// One JSON object per line
+    Jsonl,
+}
+
+#[derive(Debug)]
+enum CliError {
+    Io(String, io::Error),
+    Pvs(PvsError),
+}
+
+impl CliError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Io(..) | CliError::Pvs(PvsError::Io(_)) => EXIT_IO,
+            CliError::Pvs(PvsError::Xml(_)) => EXIT_XML,
+            CliError::Pvs(_) => EXIT_INVALID_PVS,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Io(context, e) => write!(f, "{}: {}", context, e),
+            CliError::Pvs(e) => write!(f, "Error parsing file: {}", e),
+        }
+    }
+}
+
+impl From<PvsError> for CliError {
+    fn from(e: PvsError) -> Self {
+        CliError::Pvs(e)
     }
 }
 
 fn main() {
-    let config = Config::new(env::args()).unwrap_or_else(|err| {
-        eprintln!("Error parsing arguments: {}", err);
-        process::exit(1);
-    });
-
-    let items = pvs::run(&config.src).unwrap_or_else(|err| {
-        eprintln!("Error parsing file: {}", err);
-        process::exit(1);
-    });
-
-    let dst = File::create(config.dst).unwrap_or_else(|err| {
-        eprintln!("Error creating destination file: {}", err);
-        process::exit(1);
-    });
-
-    serde_json::to_writer(BufWriter::new(dst), &items).unwrap_or_else(|err| {
-        eprintln!("Error serializing to JSON: {}", err);
-        process::exit(1);
-    });
+    let cli = Cli::parse_from(with_default_command(env::args_os().collect()));
+    env_logger::Builder::new()
+        .filter_level(cli.log_level())
+        .format_timestamp(None)
+        .parse_default_env()
+        .init();
+
+    let result = match &cli.command {
+        Command::Convert(args) => convert(args),
+        Command::Inspect(args) => inspect(args),
+        Command::Validate(args) => validate(args, cli.quiet),
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(err.exit_code());
+    }
+}
+
+// Keeps `cli <src> <dst>` working by treating a leading positional that isn't a subcommand
+// as the start of `convert`'s arguments.
+fn with_default_command(mut args: Vec<OsString>) -> Vec<OsString> {
+    let first_positional = args
+        .iter()
+        .skip(1)
+        .find(|a| a.as_os_str() == STDIO_PATH || !a.to_string_lossy().starts_with('-'));
+    if let Some(a) = first_positional {
+        if !SUBCOMMANDS.contains(&a.to_string_lossy().as_ref()) {
+            args.insert(1, OsString::from("convert"));
+        }
+    }
+    args
+}
+
+fn convert(args: &ConvertArgs) -> Result<(), CliError> {
+    let items = args.source.items()?;
+    let dst = BufWriter::new(destination(&args.dst, io::stdout())?);
+
+    let count = match args.format {
+        Format::Json => write_json_array(dst, items),
+        Format::Jsonl => write_json_lines(dst, items),
+    }?;
+    info!("Wrote {} scene items to {}", count, args.dst);
+
+    Ok(())
+}
+
+// `stdout` is taken as a writer so tests can check what `-` writes to.
+fn destination<'a>(dst: &str, stdout: impl Write + 'a) -> Result<Box<dyn Write + 'a>, CliError> {
+    if dst == STDIO_PATH {
+        return Ok(Box::new(stdout));
+    }
+    let file = File::create(dst)
+        .map_err(|e| CliError::Io("Error creating destination file".to_string(), e))?;
+    Ok(Box::new(file))
+}
+
+fn inspect(args: &SourceArgs) -> Result<(), CliError> {
+    inspect::print_tree(BufWriter::new(io::stdout()), args.items()?)?;
+    Ok(())
+}
+
+fn validate(args: &SourceArgs, quiet: bool) -> Result<(), CliError> {
+    let mut count = 0;
+    for item in args.items()? {
+        item?;
+        count += 1;
+    }
+    if !quiet {
+        eprintln!("{} is valid ({} scene items)", args.src, count);
+    }
+
+    Ok(())
+}
+
+fn write_json_array<W: Write>(mut w: W, items: pvs::Items) -> Result<usize, CliError> {
+    let mut count = 0;
+    write(&mut w, b"[")?;
+    for item in items {
+        if count > 0 {
+            write(&mut w, b",")?;
+        }
+        write_item(&mut w, &item?)?;
+        count += 1;
+    }
+    write(&mut w, b"]")?;
+    flush(w)?;
+    Ok(count)
+}
+
+fn write_json_lines<W: Write>(mut w: W, items: pvs::Items) -> Result<usize, CliError> {
+    let mut count = 0;
+    for item in items {
+        write_item(&mut w, &item?)?;
+        write(&mut w, b"\n")?;
+        count += 1;
+    }
+    flush(w)?;
+    Ok(count)
+}
+
+fn write_item<W: Write>(w: &mut W, item: &pvs::SceneItem) -> Result<(), CliError> {
+    serde_json::to_writer(w, item)
+        .map_err(|e| CliError::Io("Error writing JSON".to_string(), e.into()))
+}
+
+fn write<W: Write>(w: &mut W, buf: &[u8]) -> Result<(), CliError> {
+    w.write_all(buf)
+        .map_err(|e| CliError::Io("Error writing JSON".to_string(), e))
+}
+
+fn flush<W: Write>(mut w: W) -> Result<(), CliError> {
+    w.flush()
+        .map_err(|e| CliError::Io("Error writing JSON".to_string(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(a: &[&str]) -> Vec<OsString> {
+        a.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn positional_args_default_to_convert() {
+        assert_eq!(
+            args(&["cli", "convert", "a.xml", "b.json"]),
+            with_default_command(args(&["cli", "a.xml", "b.json"]))
+        );
+        assert_eq!(
+            args(&["cli", "convert", "-v", "-", "-"]),
+            with_default_command(args(&["cli", "-v", "-", "-"]))
+        );
+    }
+
+    const PVS: &str = r#"<PV_FILE><section_structure>
+        <component name="A"><shape_source file_name="a.ol"/></component>
+        <component name="Root">
+            <component_instance index="0" id="1"/>
+            <component_instance index="0" id="2" translation="1,0,0"/>
+        </component>
+    </section_structure></PV_FILE>"#;
+
+    fn items() -> pvs::Items {
+        pvs::items_from_reader(PVS.as_bytes(), &ConvertOptions::default()).unwrap()
+    }
+
+    fn ids(values: &[serde_json::Value]) -> Vec<&str> {
+        values
+            .iter()
+            .map(|v| v["suppliedId"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn writes_json_array() {
+        let mut out = vec![];
+        assert_eq!(3, write_json_array(&mut out, items()).unwrap());
+
+        let values: Vec<serde_json::Value> = serde_json::from_slice(&out).unwrap();
+        assert_eq!(vec!["/", "/1", "/2"], ids(&values));
+    }
+
+    #[test]
+    fn writes_one_json_object_per_line() {
+        let mut out = vec![];
+        assert_eq!(3, write_json_lines(&mut out, items()).unwrap());
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.ends_with("}\n"));
+        let values: Vec<serde_json::Value> = out
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(vec!["/", "/1", "/2"], ids(&values));
+    }
+
+    #[test]
+    fn exit_codes_follow_the_error_class() {
+        let io_error = || io::Error::new(io::ErrorKind::NotFound, "missing");
+        let xml_error = pvs::items_from_reader(b"<PV_FILE>".as_slice(), &ConvertOptions::default())
+            .err()
+            .unwrap();
+        assert!(matches!(xml_error, PvsError::Xml(_)));
+
+        assert_eq!(
+            EXIT_IO,
+            CliError::Io("Error opening source file".to_string(), io_error()).exit_code()
+        );
+        assert_eq!(EXIT_IO, CliError::Pvs(PvsError::Io(io_error())).exit_code());
+        assert_eq!(EXIT_XML, CliError::Pvs(xml_error).exit_code());
+        assert_eq!(
+            EXIT_INVALID_PVS,
+            CliError::Pvs(PvsError::EmptyStructure).exit_code()
+        );
+        assert_eq!([3, 4, 5], [EXIT_IO, EXIT_XML, EXIT_INVALID_PVS]);
+    }
+
+    #[test]
+    fn dash_writes_to_stdout() {
+        let mut stdout = Vec::new();
+        destination(STDIO_PATH, &mut stdout)
+            .unwrap()
+            .write_all(b"[]")
+            .unwrap();
+        assert_eq!(b"[]", stdout.as_slice());
+    }
+
+    #[test]
+    fn subcommands_and_flags_are_unchanged() {
+        for a in &[
+            vec!["cli", "validate", "a.xml"],
+            vec!["cli", "-q", "inspect", "a.xml"],
+            vec!["cli", "--help"],
+            vec!["cli"],
+        ] {
+            assert_eq!(args(a), with_default_command(args(a)));
+        }
+    }
 }
@@ -0,0 +1,54 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+const CHILDREN: usize = 1000;
+
+// A rotated, scaled and translated placement, like a typical PVS instance.
+fn placement(i: usize) -> [[f32; 4]; 4] {
+    let (sin, cos) = (i as f32 * 0.01).sin_cos();
+    [
+        [cos, -sin, 0.0, i as f32],
+        [sin, cos, 0.0, 2.0 * i as f32],
+        [0.0, 0.0, 1.5, -(i as f32)],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
+
+fn multiply(c: &mut Criterion) {
+    let parent = placement(7);
+    let children: Vec<[[f32; 4]; 4]> = (0..CHILDREN).map(placement).collect();
+    for child in &children {
+        assert_eq!(
+            core::multiply_4x4_scalar(parent, *child),
+            core::multiply_4x4(parent, *child)
+        );
+    }
+
+    let mut group = c.benchmark_group("multiply_4x4");
+    group.throughput(Throughput::Elements(CHILDREN as u64));
+    group.bench_function("scalar", |b| {
+        b.iter(|| {
+            for child in &children {
+                black_box(core::multiply_4x4_scalar(black_box(parent), *child));
+            }
+        })
+    });
+    group.bench_function("simd", |b| {
+        b.iter(|| {
+            for child in &children {
+                black_box(core::multiply_4x4(black_box(parent), *child));
+            }
+        })
+    });
+    group.bench_function("batch", |b| {
+        let mut out = children.clone();
+        b.iter(|| {
+            out.copy_from_slice(&children);
+            core::multiply_4x4_batch(black_box(parent), &mut out);
+            black_box(&out);
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, multiply);
+criterion_main!(benches);
@@ -0,0 +1,118 @@
+use serde::Serialize;
+
+use crate::Vector3f;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct BoundingBox3 {
+    pub min: Vector3f,
+    pub max: Vector3f,
+}
+
+impl BoundingBox3 {
+    pub fn new(min: Vector3f, max: Vector3f) -> BoundingBox3 {
+        BoundingBox3 { min, max }
+    }
+
+    pub fn from_arr_6(a: [f32; 6]) -> BoundingBox3 {
+        BoundingBox3::new(
+            Vector3f::new(a[0], a[1], a[2]),
+            Vector3f::new(a[3], a[4], a[5]),
+        )
+    }
+
+    pub fn union(&self, other: &BoundingBox3) -> BoundingBox3 {
+        BoundingBox3::new(
+            Vector3f::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            Vector3f::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        )
+    }
+
+    pub fn intersection(&self, other: &BoundingBox3) -> Option<BoundingBox3> {
+        let bbox = BoundingBox3::new(
+            Vector3f::new(
+                self.min.x.max(other.min.x),
+                self.min.y.max(other.min.y),
+                self.min.z.max(other.min.z),
+            ),
+            Vector3f::new(
+                self.max.x.min(other.max.x),
+                self.max.y.min(other.max.y),
+                self.max.z.min(other.max.z),
+            ),
+        );
+        if bbox.min.x > bbox.max.x || bbox.min.y > bbox.max.y || bbox.min.z > bbox.max.z {
+            None
+        } else {
+            Some(bbox)
+        }
+    }
+
+    pub fn contains_point(&self, p: Vector3f) -> bool {
+        (self.min.x..=self.max.x).contains(&p.x)
+            && (self.min.y..=self.max.y).contains(&p.y)
+            && (self.min.z..=self.max.z).contains(&p.z)
+    }
+
+    pub fn contains(&self, other: &BoundingBox3) -> bool {
+        self.contains_point(other.min) && self.contains_point(other.max)
+    }
+
+    pub fn center(&self) -> Vector3f {
+        Vector3f::new(
+            (self.min.x + self.max.x) / 2.0,
+            (self.min.y + self.max.y) / 2.0,
+            (self.min.z + self.max.z) / 2.0,
+        )
+    }
+
+    // Full size along each axis, not the half-size.
+    pub fn extent(&self) -> Vector3f {
+        Vector3f::new(
+            self.max.x - self.min.x,
+            self.max.y - self.min.y,
+            self.max.z - self.min.z,
+        )
+    }
+
+    pub fn corners(&self) -> [Vector3f; 8] {
+        let (lo, hi) = (self.min, self.max);
+        [
+            Vector3f::new(lo.x, lo.y, lo.z),
+            Vector3f::new(hi.x, lo.y, lo.z),
+            Vector3f::new(lo.x, hi.y, lo.z),
+            Vector3f::new(hi.x, hi.y, lo.z),
+            Vector3f::new(lo.x, lo.y, hi.z),
+            Vector3f::new(hi.x, lo.y, hi.z),
+            Vector3f::new(lo.x, hi.y, hi.z),
+            Vector3f::new(hi.x, hi.y, hi.z),
+        ]
+    }
+
+    // Transforms all 8 corners and bounds the result, so rotated boxes stay conservative.
+    pub fn transform(&self, t: [[f32; 4]; 4]) -> BoundingBox3 {
+        let corners = self.corners();
+        let first = transform_point(t, corners[0]);
+        corners[1..]
+            .iter()
+            .fold(BoundingBox3::new(first, first), |bbox, c| {
+                let p = transform_point(t, *c);
+                bbox.union(&BoundingBox3::new(p, p))
+            })
+    }
+}
+
+fn transform_point(t: [[f32; 4]; 4], p: Vector3f) -> Vector3f {
+    Vector3f::new(
+        t[0][0] * p.x + t[0][1] * p.y + t[0][2] * p.z + t[0][3],
+        t[1][0] * p.x + t[1][1] * p.y + t[1][2] * p.z + t[1][3],
+        t[2][0] * p.x + t[2][1] * p.y + t[2][2] * p.z + t[2][3],
+    )
+}
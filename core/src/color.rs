@@ -0,0 +1,178 @@
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::parse_floats;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Color3 {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ColorError {
+    pub value: String,
+}
+
+impl fmt::Display for ColorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid color '{}', expected #RRGGBB, r,g,b fractions or a packed integer",
+            self.value
+        )
+    }
+}
+
+impl Error for ColorError {}
+
+impl Color3 {
+    pub const BLACK: Color3 = Color3 { r: 0, g: 0, b: 0 };
+    pub const WHITE: Color3 = Color3 {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+
+    pub fn new(r: u8, g: u8, b: u8) -> Color3 {
+        Color3 { r, g, b }
+    }
+
+    pub fn from_hex(s: &str) -> Result<Color3, ColorError> {
+        let error = || ColorError {
+            value: s.to_string(),
+        };
+        let hex = s.trim().strip_prefix('#').ok_or_else(error)?;
+        if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(error());
+        }
+
+        u32::from_str_radix(hex, 16)
+            .map(Color3::from_packed)
+            .map_err(|_| error())
+    }
+
+    pub fn to_hex(self) -> String {
+        format!("#{:02X}{:02X}{:02X}", self.r, self.g, self.b)
+    }
+
+    // 0xRRGGBB, anything above the low 24 bits is ignored.
+    pub fn from_packed(packed: u32) -> Color3 {
+        Color3 {
+            r: (packed >> 16) as u8,
+            g: (packed >> 8) as u8,
+            b: packed as u8,
+        }
+    }
+
+    pub fn to_packed(self) -> u32 {
+        (u32::from(self.r) << 16) | (u32::from(self.g) << 8) | u32::from(self.b)
+    }
+
+    // Fractions between 0 and 1 such as "0.5,0.2,1.0", separated as parse_floats accepts.
+    pub fn from_floats(s: &str) -> Result<Color3, ColorError> {
+        parse_floats::<3>(s)
+            .map(Color3::from_unit)
+            .map_err(|_| ColorError {
+                value: s.to_string(),
+            })
+    }
+
+    // Channels as stored, without any gamma handling, scaled to fractions between 0 and 1.
+    pub fn from_unit([r, g, b]: [f32; 3]) -> Color3 {
+        Color3 {
+            r: unit_to_u8(r),
+            g: unit_to_u8(g),
+            b: unit_to_u8(b),
+        }
+    }
+
+    pub fn to_unit(self) -> [f32; 3] {
+        [self.r, self.g, self.b].map(|c| f32::from(c) / f32::from(u8::MAX))
+    }
+
+    // Colors are stored sRGB encoded, so linear values are gamma encoded on the way in.
+    pub fn from_linear(linear: [f32; 3]) -> Color3 {
+        Color3::from_unit(linear.map(linear_to_srgb))
+    }
+
+    pub fn to_linear(self) -> [f32; 3] {
+        self.to_unit().map(srgb_to_linear)
+    }
+}
+
+// Accepts any of the formats above, packed integers in decimal or with a 0x prefix.
+impl FromStr for Color3 {
+    type Err = ColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.starts_with('#') {
+            return Color3::from_hex(s);
+        }
+        // Any separator parse_floats accepts means several tokens, so fractions.
+        if s.contains([',', ';']) || s.contains(char::is_whitespace) {
+            return Color3::from_floats(s);
+        }
+
+        let packed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => s.parse(),
+        };
+        match packed {
+            Ok(p) if p <= 0xFF_FFFF => Ok(Color3::from_packed(p)),
+            _ => Err(ColorError {
+                value: s.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct ColorMaterial {
+    pub ambient: Color3,
+    pub diffuse: Color3,
+    pub emissive: Color3,
+    pub glossiness: u8,
+    pub opacity: u8,
+    pub specular: Color3,
+}
+
+// A plain opaque white material, which is what renderers assume when an item has none.
+impl Default for ColorMaterial {
+    fn default() -> Self {
+        ColorMaterial {
+            ambient: Color3::BLACK,
+            diffuse: Color3::WHITE,
+            emissive: Color3::BLACK,
+            glossiness: 0,
+            opacity: u8::MAX,
+            specular: Color3::BLACK,
+        }
+    }
+}
+
+// Maps a fraction between 0 and 1 onto the full u8 range, clamping values outside of it.
+pub fn unit_to_u8(f: f32) -> u8 {
+    (f.clamp(0.0, 1.0) * f32::from(u8::MAX)).round() as u8
+}
+
+// The piecewise sRGB transfer functions from IEC 61966-2-1.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
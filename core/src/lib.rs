@@ -1,6 +1,22 @@
 use serde::Serialize;
-use std::convert::TryInto;
-use std::error::Error;
+use std::ops::{Add, Mul};
+
+mod bounds;
+mod color;
+mod matrix;
+mod parse;
+mod rotation;
+#[cfg(target_arch = "x86_64")]
+mod simd;
+
+pub use bounds::BoundingBox3;
+pub use color::{linear_to_srgb, srgb_to_linear, unit_to_u8, Color3, ColorError, ColorMaterial};
+pub use matrix::{Decomposition, Matrix4};
+pub use parse::{parse_doubles, parse_floats, to_float_arr, Float, ParseError};
+pub use rotation::{
+    determinant_3x3, is_orthonormal, orthonormality_error, orthonormalize, validate_rotation,
+    EulerOrder, Quaternion, Rotation3, RotationError,
+};
 
 pub const EMPTY_STR: &str = "";
 
@@ -16,6 +32,19 @@ impl Matrix4x4 {
     ];
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Vector3f {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vector3f {
+    pub fn new(x: f32, y: f32, z: f32) -> Vector3f {
+        Vector3f { x, y, z }
+    }
+}
+
 #[derive(Debug, PartialEq, Serialize)]
 pub struct Vector4f {
     pub x: f32,
@@ -32,62 +61,101 @@ pub struct Transform {
     pub r3: Vector4f,
 }
 
-#[derive(Debug, PartialEq, Serialize)]
-pub struct Color3 {
-    pub r: u8,
-    pub g: u8,
-    pub b: u8,
+pub fn is_4x4_identity(transform: [[f32; 4]; 4]) -> bool {
+    transform == Matrix4x4::IDENTITY
 }
 
-#[derive(Debug, PartialEq, Serialize)]
-pub struct ColorMaterial {
-    pub ambient: Color3,
-    pub diffuse: Color3,
-    pub emissive: Color3,
-    pub glossiness: u8,
-    pub opacity: u8,
-    pub specular: Color3,
+pub fn to_f64_4x4(m: [[f32; 4]; 4]) -> [[f64; 4]; 4] {
+    m.map(|row| row.map(f64::from))
 }
 
-pub fn is_4x4_identity(transform: [[f32; 4]; 4]) -> bool {
-    return transform == Matrix4x4::IDENTITY;
+// Rounds to the nearest f32, which is how double precision results are emitted.
+pub fn to_f32_4x4(m: [[f64; 4]; 4]) -> [[f32; 4]; 4] {
+    m.map(|row| row.map(|v| v as f32))
+}
+
+pub fn approx_eq(a: f32, b: f32, epsilon: f32) -> bool {
+    (a - b).abs() <= epsilon
+}
+
+pub fn approx_eq_4x4(xs: [[f32; 4]; 4], ys: [[f32; 4]; 4], epsilon: f32) -> bool {
+    xs.iter()
+        .flatten()
+        .zip(ys.iter().flatten())
+        .all(|(&x, &y)| approx_eq(x, y, epsilon))
+}
+
+pub fn is_identity_within(transform: [[f32; 4]; 4], epsilon: f32) -> bool {
+    approx_eq_4x4(transform, Matrix4x4::IDENTITY, epsilon)
+}
+
+// Replaces values within epsilon of 0, 1 or -1 with exactly that value, which cleans up
+// rounding noise such as 1e-29 translations left over from exporters.
+pub fn snap(value: f32, epsilon: f32) -> f32 {
+    [0.0, 1.0, -1.0]
+        .iter()
+        .copied()
+        .find(|&target| approx_eq(value, target, epsilon))
+        .unwrap_or(value)
 }
 
+pub fn snap_4x4(transform: [[f32; 4]; 4], epsilon: f32) -> [[f32; 4]; 4] {
+    transform.map(|row| row.map(|v| snap(v, epsilon)))
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline]
 pub fn multiply_4x4(xs: [[f32; 4]; 4], ys: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
-    let mut result: [[f32; 4]; 4] = Matrix4x4::ZERO;
-    for r in 0..4 {
-        for c in 0..4 {
-            for i in 0..4 {
-                result[r][c] += xs[r][i] * ys[i][c];
-            }
-        }
-    }
+    simd::multiply_4x4(xs, ys)
+}
 
-    return result;
+#[cfg(not(target_arch = "x86_64"))]
+#[inline]
+pub fn multiply_4x4(xs: [[f32; 4]; 4], ys: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
+    multiply_4x4_scalar(xs, ys)
 }
 
-pub fn to_arr_3<T>(v: Vec<T>) -> [T; 3] {
-    v.try_into()
-        .unwrap_or_else(|v: Vec<T>| panic!("Expected Vec of length {} but was {}", 3, v.len()))
+// Replaces every child with parent * child, as when placing many instances in one assembly.
+#[cfg(target_arch = "x86_64")]
+pub fn multiply_4x4_batch(parent: [[f32; 4]; 4], children: &mut [[[f32; 4]; 4]]) {
+    simd::multiply_4x4_batch(parent, children)
 }
 
-pub fn to_arr_9<T>(v: Vec<T>) -> [T; 9] {
-    v.try_into()
-        .unwrap_or_else(|v: Vec<T>| panic!("Expected Vec of length {} but was {}", 9, v.len()))
+#[cfg(not(target_arch = "x86_64"))]
+pub fn multiply_4x4_batch(parent: [[f32; 4]; 4], children: &mut [[[f32; 4]; 4]]) {
+    for child in children.iter_mut() {
+        *child = multiply_4x4_scalar(parent, *child);
+    }
 }
 
-pub fn to_float_arr(a: &str) -> Result<Vec<f32>, Box<dyn Error>> {
-    let parts: Vec<&str> = a.split(",").collect();
-    let mut fs: Vec<f32> = vec![];
-    for n in parts {
-        fs.push(n.parse()?);
+// Reference implementation the SIMD versions must match exactly.
+pub fn multiply_4x4_scalar(xs: [[f32; 4]; 4], ys: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
+    multiply(xs, ys)
+}
+
+// For accumulating deep hierarchies far from the origin without losing precision.
+pub fn multiply_4x4_f64(xs: [[f64; 4]; 4], ys: [[f64; 4]; 4]) -> [[f64; 4]; 4] {
+    multiply(xs, ys)
+}
+
+fn multiply<T>(xs: [[T; 4]; 4], ys: [[T; 4]; 4]) -> [[T; 4]; 4]
+where
+    T: Copy + Default + Add<Output = T> + Mul<Output = T>,
+{
+    let mut result = [[T::default(); 4]; 4];
+    for r in 0..4 {
+        for c in 0..4 {
+            for i in 0..4 {
+                result[r][c] = result[r][c] + xs[r][i] * ys[i][c];
+            }
+        }
     }
 
-    return Ok(fs);
+    result
 }
 
 pub fn to_transform(t: [[f32; 4]; 4]) -> Transform {
-    return Transform {
+    Transform {
         r0: Vector4f {
             x: t[0][0],
             y: t[0][1],
@@ -112,7 +180,7 @@ pub fn to_transform(t: [[f32; 4]; 4]) -> Transform {
             z: t[3][2],
             w: t[3][3],
         },
-    };
+    }
 }
 
 #[cfg(test)]
@@ -139,12 +207,12 @@ mod tests {
 
     #[test]
     fn false_if_not_identity() {
-        assert_eq!(is_4x4_identity(Matrix4x4::ZERO), false);
+        assert!(!is_4x4_identity(Matrix4x4::ZERO));
     }
 
     #[test]
     fn true_if_identity() {
-        assert_eq!(is_4x4_identity(Matrix4x4::IDENTITY), true);
+        assert!(is_4x4_identity(Matrix4x4::IDENTITY));
     }
 
     #[test]
@@ -188,4 +256,597 @@ mod tests {
             ]
         );
     }
+
+    fn bbox(min: [f32; 3], max: [f32; 3]) -> BoundingBox3 {
+        BoundingBox3::new(
+            Vector3f::new(min[0], min[1], min[2]),
+            Vector3f::new(max[0], max[1], max[2]),
+        )
+    }
+
+    #[test]
+    fn bbox_union() {
+        assert_eq!(
+            bbox([-1.0, 0.0, -2.0], [1.0, 3.0, 1.0])
+                .union(&bbox([0.0, -1.0, 0.0], [2.0, 1.0, 4.0])),
+            bbox([-1.0, -1.0, -2.0], [2.0, 3.0, 4.0])
+        );
+    }
+
+    #[test]
+    fn bbox_intersection() {
+        assert_eq!(
+            bbox([-1.0, 0.0, -2.0], [1.0, 3.0, 1.0])
+                .intersection(&bbox([0.0, -1.0, 0.0], [2.0, 1.0, 4.0])),
+            Some(bbox([0.0, 0.0, 0.0], [1.0, 1.0, 1.0]))
+        );
+    }
+
+    #[test]
+    fn bbox_disjoint_intersection() {
+        assert_eq!(
+            bbox([0.0; 3], [1.0; 3]).intersection(&bbox([2.0; 3], [3.0; 3])),
+            None
+        );
+    }
+
+    #[test]
+    fn bbox_contains() {
+        let outer = bbox([-2.0; 3], [2.0; 3]);
+
+        assert!(outer.contains(&bbox([-1.0; 3], [2.0; 3])));
+        assert!(!outer.contains(&bbox([-1.0; 3], [3.0; 3])));
+        assert!(outer.contains_point(Vector3f::new(2.0, 0.0, -2.0)));
+        assert!(!outer.contains_point(Vector3f::new(0.0, 2.5, 0.0)));
+    }
+
+    #[test]
+    fn bbox_center_and_extent() {
+        let b = bbox([-1.0, 0.0, 2.0], [3.0, 4.0, 3.0]);
+
+        assert_eq!(b.center(), Vector3f::new(1.0, 2.0, 2.5));
+        assert_eq!(b.extent(), Vector3f::new(4.0, 4.0, 1.0));
+    }
+
+    #[test]
+    fn bbox_transform_identity() {
+        let b = bbox([-1.0, 0.0, 2.0], [3.0, 4.0, 3.0]);
+
+        assert_eq!(b.transform(Matrix4x4::IDENTITY), b);
+    }
+
+    #[test]
+    fn bbox_transform() {
+        assert_eq!(
+            bbox([0.0, 0.0, 0.0], [1.0, 2.0, 3.0]).transform([
+                [0.0, -1.0, 0.0, 10.0],
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, -5.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ]),
+            bbox([8.0, 0.0, -5.0], [10.0, 1.0, -2.0])
+        );
+    }
+
+    #[test]
+    fn color_hex_round_trip() {
+        let color = Color3::new(0x12, 0xAB, 0xFF);
+
+        assert_eq!("#12ABFF", color.to_hex());
+        assert_eq!(Ok(color), Color3::from_hex("#12abff"));
+        assert_eq!(Ok(color), Color3::from_hex(&color.to_hex()));
+        for invalid in ["12ABFF", "#12ABF", "#12ABFG", "#12ABFF0"] {
+            assert!(Color3::from_hex(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn color_packed_round_trip() {
+        let color = Color3::new(1, 2, 3);
+
+        assert_eq!(0x010203, color.to_packed());
+        assert_eq!(color, Color3::from_packed(color.to_packed()));
+        assert_eq!(color, Color3::from_packed(0xFF01_0203));
+    }
+
+    #[test]
+    fn color_floats_round_trip() {
+        assert_eq!(
+            Ok(Color3::new(128, 51, 255)),
+            Color3::from_floats("0.5, 0.2,1.0")
+        );
+        assert!(Color3::from_floats("0.5,0.2").is_err());
+        assert!(Color3::from_floats("0.5,0.2,1,0").is_err());
+        assert!(Color3::from_floats("0.5,x,1").is_err());
+        for r in 0..=u8::MAX {
+            let color = Color3::new(r, u8::MAX - r, r / 2);
+            assert_eq!(color, Color3::from_unit(color.to_unit()));
+        }
+    }
+
+    #[test]
+    fn color_parses_any_format() {
+        let color = Color3::new(255, 0, 128);
+
+        assert_eq!(Ok(color), "#FF0080".parse());
+        assert_eq!(Ok(color), "1,0,0.5".parse());
+        assert_eq!(Ok(color), "1 0 0.5".parse());
+        assert_eq!(Ok(color), "1;0;0.5".parse());
+        assert_eq!(Ok(color), "0xFF0080".parse());
+        assert_eq!(Ok(color), "16711808".parse());
+        assert_eq!(
+            "Invalid color '16777216', expected #RRGGBB, r,g,b fractions or a packed integer",
+            "16777216".parse::<Color3>().unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn color_linear_round_trip() {
+        assert_eq!([0.0, 1.0], [srgb_to_linear(0.0), srgb_to_linear(1.0)]);
+        assert!((srgb_to_linear(0.5) - 0.214_041).abs() < 1e-5);
+        assert!((linear_to_srgb(0.214_041) - 0.5).abs() < 1e-5);
+        for r in 0..=u8::MAX {
+            let color = Color3::new(r, u8::MAX - r, r / 3);
+            assert_eq!(color, Color3::from_linear(color.to_linear()));
+        }
+    }
+
+    #[test]
+    fn unit_to_u8_clamps() {
+        assert_eq!(
+            [0, 128, 255, 0, 255],
+            [0.0, 0.5, 1.0, -0.2, 1.7].map(unit_to_u8)
+        );
+    }
+
+    const EPSILON: f32 = 1e-5;
+
+    fn assert_matrix_eq(expected: Matrix4, actual: Matrix4) {
+        assert!(
+            approx_eq_4x4(expected.0, actual.0, EPSILON),
+            "{:?} != {:?}",
+            expected,
+            actual
+        );
+    }
+
+    // Rotates 90 degrees about z, scales by (2, 3, 4) and translates by (10, 20, 30).
+    fn trs() -> Decomposition {
+        Decomposition {
+            translation: Vector3f::new(10.0, 20.0, 30.0),
+            rotation: [[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
+            scale: Vector3f::new(2.0, 3.0, 4.0),
+        }
+    }
+
+    #[test]
+    fn matrix4_transpose_and_determinant() {
+        let m = Matrix4([
+            [2.0, 0.0, 1.0, 3.0],
+            [1.0, 3.0, 2.0, 0.0],
+            [1.0, 1.0, 2.0, 1.0],
+            [0.0, 2.0, 0.0, 1.0],
+        ]);
+
+        assert_eq!(m, m.transpose().transpose());
+        assert_eq!(m.0[0][3], m.transpose().0[3][0]);
+        assert_eq!(12.0, m.determinant());
+        assert_eq!(m.determinant(), m.transpose().determinant());
+        assert_eq!(24.0, Matrix4::from_trs(&trs()).determinant());
+    }
+
+    #[test]
+    fn matrix4_inverse() {
+        let m = Matrix4([
+            [2.0, 0.0, 1.0, 3.0],
+            [1.0, 3.0, 2.0, 0.0],
+            [1.0, 1.0, 2.0, 1.0],
+            [0.0, 2.0, 0.0, 1.0],
+        ]);
+
+        assert_matrix_eq(Matrix4::IDENTITY, m * m.inverse().unwrap());
+        assert_matrix_eq(Matrix4::IDENTITY, m.inverse().unwrap() * m);
+        let world = Matrix4::from_trs(&trs());
+        let local = world.inverse().unwrap();
+        let p = Vector3f::new(1.0, 2.0, 3.0);
+        let back = local.transform_point(world.transform_point(p));
+        assert!((back.x - p.x).abs() < 1e-5 && (back.y - p.y).abs() < 1e-5);
+        assert_eq!(None, Matrix4(Matrix4x4::ZERO).inverse());
+        assert_eq!(
+            None,
+            Matrix4::from_scale(Vector3f::new(1.0, 0.0, 1.0)).inverse()
+        );
+    }
+
+    #[test]
+    fn matrix4_transforms_points_and_vectors() {
+        let m = Matrix4::from_trs(&trs());
+
+        assert_eq!(
+            Vector3f::new(10.0, 22.0, 30.0),
+            m.transform_point(Vector3f::new(1.0, 0.0, 0.0))
+        );
+        assert_eq!(
+            Vector3f::new(7.0, 20.0, 30.0),
+            m * Vector3f::new(0.0, 1.0, 0.0)
+        );
+        assert_eq!(
+            Vector3f::new(0.0, 0.0, 4.0),
+            m.transform_vector(Vector3f::new(0.0, 0.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn matrix4_decomposes_trs() {
+        let m = Matrix4::from_trs(&trs());
+
+        assert_eq!(Some(trs()), m.decompose());
+        assert_matrix_eq(m, Matrix4::from_trs(&m.decompose().unwrap()));
+        let mirrored = m * Matrix4::from_scale(Vector3f::new(-1.0, 1.0, 1.0));
+        let d = mirrored.decompose().unwrap();
+        assert_eq!(Vector3f::new(-2.0, 3.0, 4.0), d.scale);
+        assert_eq!(trs().rotation, d.rotation);
+        assert_eq!(
+            None,
+            Matrix4::from_scale(Vector3f::new(0.0, 1.0, 1.0)).decompose()
+        );
+        assert_eq!(
+            None,
+            Matrix4::from_scale(Vector3f::new(1.0, 1e-7, 1.0)).decompose()
+        );
+    }
+
+    #[test]
+    fn matrix4_does_not_decompose_shear() {
+        let sheared = Matrix4::from_rotation([[1.0, 0.5, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+
+        assert_eq!(None, sheared.decompose());
+        assert_eq!(None, (Matrix4::from_trs(&trs()) * sheared).decompose());
+    }
+
+    #[test]
+    fn matrix4_converts_to_and_from_transform() {
+        let m = Matrix4::from_trs(&trs());
+        let transform = Transform::from(m);
+
+        assert_eq!(30.0, transform.r2.w);
+        assert_eq!(m, Matrix4::from(&transform));
+        assert_eq!(m, Matrix4::from(transform));
+        assert_eq!(Matrix4::IDENTITY, Matrix4::from(Matrix4x4::IDENTITY));
+        assert_eq!(multiply_4x4(m.0, m.0), <[[f32; 4]; 4]>::from(m * m));
+    }
+
+    #[test]
+    fn approx_eq_within_epsilon() {
+        assert!(approx_eq(1.0, 1.0 + 1e-7, 1e-6));
+        assert!(!approx_eq(1.0, 1.0 + 1e-5, 1e-6));
+        assert!(approx_eq(0.0, 1.799_372_5e-26, 1e-6));
+        assert!(approx_eq_4x4(Matrix4x4::IDENTITY, Matrix4x4::IDENTITY, 0.0));
+    }
+
+    #[test]
+    fn identity_within_epsilon() {
+        let mut near = Matrix4x4::IDENTITY;
+        near[1][3] = 1.799_372_5e-26;
+        near[2][2] = 0.999_999_9;
+
+        assert!(!is_4x4_identity(near));
+        assert!(is_identity_within(near, 1e-6));
+        near[0][3] = 0.01;
+        assert!(!is_identity_within(near, 1e-6));
+    }
+
+    #[test]
+    fn snaps_near_zero_and_one() {
+        assert_eq!(
+            [0.0, 1.0, -1.0, 0.5, 45.0],
+            [1e-9, 1.000_000_1, -0.999_999_9, 0.5, 45.0].map(|v| snap(v, 1e-6))
+        );
+        let mut near = Matrix4x4::IDENTITY;
+        near[1][3] = 1.799_372_5e-26;
+        near[2][3] = 30.0;
+        let mut expected = Matrix4x4::IDENTITY;
+        expected[2][3] = 30.0;
+        assert_eq!(expected, snap_4x4(near, 1e-6));
+    }
+
+    fn assert_rotation_eq(expected: &Rotation3, actual: &Rotation3) {
+        assert_matrix_eq(
+            Matrix4::from_rotation(*expected),
+            Matrix4::from_rotation(*actual),
+        );
+    }
+
+    fn assert_vector_eq(expected: Vector3f, actual: Vector3f) {
+        assert_matrix_eq(
+            Matrix4::from_translation(expected),
+            Matrix4::from_translation(actual),
+        );
+    }
+
+    #[test]
+    fn quaternion_rotation_round_trip() {
+        let z90 = [[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]];
+        let q = Quaternion::from_rotation(&z90);
+
+        assert_rotation_eq(&z90, &q.to_rotation());
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert!(approx_eq(half, q.z, 1e-6) && approx_eq(half, q.w, 1e-6));
+        // Every branch of the trace-based conversion, including 180 degree turns.
+        for (axis, angle) in [
+            (Vector3f::new(1.0, 0.0, 0.0), std::f32::consts::PI),
+            (Vector3f::new(0.0, 1.0, 0.0), std::f32::consts::PI),
+            (Vector3f::new(0.0, 0.0, 1.0), std::f32::consts::PI),
+            (Vector3f::new(1.0, 2.0, 3.0), 2.5),
+            (Vector3f::new(-1.0, 0.5, 0.0), 0.3),
+        ] {
+            let m = Quaternion::from_axis_angle(axis, angle).to_rotation();
+            assert_rotation_eq(&m, &Quaternion::from_rotation(&m).to_rotation());
+        }
+    }
+
+    #[test]
+    fn quaternion_normalize_and_multiply() {
+        let q = Quaternion::new(0.0, 0.0, 3.0, 4.0).normalize();
+
+        assert_eq!(Quaternion::new(0.0, 0.0, 0.6, 0.8), q);
+        assert_eq!(
+            Quaternion::IDENTITY,
+            Quaternion::new(0.0, 0.0, 0.0, 0.0).normalize()
+        );
+        let z = |angle| Quaternion::from_axis_angle(Vector3f::new(0.0, 0.0, 1.0), angle);
+        let product = (z(0.25) * z(0.5)).to_rotation();
+        assert_rotation_eq(&z(0.75).to_rotation(), &product);
+        assert_rotation_eq(
+            &Quaternion::IDENTITY.to_rotation(),
+            &(q * q.conjugate()).to_rotation(),
+        );
+    }
+
+    #[test]
+    fn quaternion_slerp() {
+        let z = |angle| Quaternion::from_axis_angle(Vector3f::new(0.0, 0.0, 1.0), angle);
+        let (a, b) = (z(0.0), z(1.0));
+
+        assert_eq!(a, a.slerp(&b, 0.0));
+        assert_rotation_eq(&b.to_rotation(), &a.slerp(&b, 1.0).to_rotation());
+        assert_rotation_eq(&z(0.25).to_rotation(), &a.slerp(&b, 0.25).to_rotation());
+        let flipped = Quaternion::new(-b.x, -b.y, -b.z, -b.w);
+        assert_rotation_eq(&z(0.5).to_rotation(), &a.slerp(&flipped, 0.5).to_rotation());
+        assert_eq!(a, a.slerp(&a, 0.5));
+    }
+
+    #[test]
+    fn quaternion_euler_round_trip() {
+        let angles = Vector3f::new(0.3, -0.7, 1.2);
+        for order in [EulerOrder::Xyz, EulerOrder::Zyx] {
+            let q = Quaternion::from_euler(angles, order);
+            assert_vector_eq(angles, q.to_euler(order));
+        }
+
+        // Xyz applies x about the world axis last, Zyx applies z last.
+        let x = Quaternion::from_axis_angle(Vector3f::new(1.0, 0.0, 0.0), 0.5);
+        let z = Quaternion::from_axis_angle(Vector3f::new(0.0, 0.0, 1.0), 0.4);
+        let angles = Vector3f::new(0.5, 0.0, 0.4);
+        assert_rotation_eq(
+            &(x * z).to_rotation(),
+            &Quaternion::from_euler(angles, EulerOrder::Xyz).to_rotation(),
+        );
+        assert_rotation_eq(
+            &(z * x).to_rotation(),
+            &Quaternion::from_euler(angles, EulerOrder::Zyx).to_rotation(),
+        );
+    }
+
+    #[test]
+    fn quaternion_euler_at_gimbal_lock() {
+        let half_pi = std::f32::consts::FRAC_PI_2;
+        for order in [EulerOrder::Xyz, EulerOrder::Zyx] {
+            let q = Quaternion::from_euler(Vector3f::new(0.4, half_pi, 0.2), order);
+            let angles = q.to_euler(order);
+            assert_rotation_eq(
+                &q.to_rotation(),
+                &Quaternion::from_euler(angles, order).to_rotation(),
+            );
+        }
+    }
+
+    #[test]
+    fn validates_rotations() {
+        let z90 = [[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]];
+        let mirrored = [[-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        let scaled = [[2.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        let skewed = [[1.0, 0.5, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+        assert_eq!(1.0, determinant_3x3(&z90));
+        assert_eq!(-1.0, determinant_3x3(&mirrored));
+        assert!(is_orthonormal(&z90, 1e-6));
+        assert!(is_orthonormal(&mirrored, 1e-6));
+        assert!(!is_orthonormal(&scaled, 1e-6));
+        assert_eq!(0.5, orthonormality_error(&skewed));
+        let nan = [[f32::NAN, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        let infinite = [[1.0, f32::INFINITY, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        assert_eq!(f32::INFINITY, orthonormality_error(&nan));
+        assert!(!is_orthonormal(&infinite, 1e-6));
+        assert!(validate_rotation(&nan, 1e-6).is_err());
+        assert_eq!(Ok(()), validate_rotation(&z90, 1e-6));
+        assert_eq!(
+            Err(RotationError::Reflection),
+            validate_rotation(&mirrored, 1e-6)
+        );
+        assert_eq!(
+            "Matrix is not orthonormal (off by 3)",
+            Quaternion::try_from_rotation(&scaled, 1e-6)
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn orthonormalizes_rotations() {
+        let skewed = [[1.0, 0.5, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.2]];
+        let mirrored = [[1.0, 0.0, 0.0], [0.0, 1.1, 0.0], [0.0, 0.0, -1.0]];
+
+        let fixed = orthonormalize(&skewed).unwrap();
+        assert!(is_orthonormal(&fixed, 1e-6));
+        assert_rotation_eq(&[[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]], &fixed);
+        let fixed = orthonormalize(&mirrored).unwrap();
+        assert!(is_orthonormal(&fixed, 1e-6));
+        assert_eq!(-1.0, determinant_3x3(&fixed));
+        assert_eq!(
+            None,
+            orthonormalize(&[[1.0, 2.0, 0.0], [0.0, 0.0, 0.0], [0.0, 0.0, 1.0]])
+        );
+    }
+
+    // Deterministic spread of magnitudes and signs, so rounding differences would show up.
+    fn matrices(count: usize) -> Vec<[[f32; 4]; 4]> {
+        let mut state: u32 = 0x2545_F491;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state as f32 / u32::MAX as f32 - 0.5) * 2000.0
+        };
+        (0..count)
+            .map(|_| [[(); 4]; 4].map(|r| r.map(|_| next())))
+            .collect()
+    }
+
+    #[test]
+    fn simd_multiply_matches_scalar() {
+        let ms = matrices(200);
+        for pair in ms.windows(2) {
+            assert_eq!(
+                multiply_4x4_scalar(pair[0], pair[1]),
+                multiply_4x4(pair[0], pair[1])
+            );
+        }
+    }
+
+    #[test]
+    fn batch_multiply_matches_scalar() {
+        let parent = matrices(1)[0];
+        let children = matrices(37);
+        let mut batch = children.clone();
+        multiply_4x4_batch(parent, &mut batch);
+
+        for (child, result) in children.iter().zip(batch.iter()) {
+            assert_eq!(multiply_4x4_scalar(parent, *child), *result);
+        }
+        multiply_4x4_batch(parent, &mut []);
+    }
+
+    #[test]
+    fn multiply_4x4_f64_keeps_precision() {
+        let mut far = Matrix4x4::IDENTITY;
+        far[0][3] = 100_000_000.0;
+        let mut near = Matrix4x4::IDENTITY;
+        near[0][3] = 0.1;
+
+        let single = multiply_4x4(far, near)[0][3];
+        let double = multiply_4x4_f64(to_f64_4x4(far), to_f64_4x4(near))[0][3];
+        assert_eq!(100_000_000.0, single);
+        assert_eq!(100_000_000.1, double);
+        assert_eq!(
+            multiply_4x4(far, near),
+            to_f32_4x4(to_f64_4x4(multiply_4x4(far, near)))
+        );
+    }
+
+    #[test]
+    fn parses_floats_in_either_precision() {
+        assert_eq!(Ok(vec![0.1f32, 2.0]), to_float_arr::<f32>("0.1,2"));
+        assert_eq!(
+            Ok(vec![0.1f64, 2.0, 3e-3]),
+            to_float_arr::<f64>("0.1; 2 3E-3")
+        );
+        assert_eq!(Ok(vec![]), to_float_arr::<f32>(""));
+        assert_eq!(
+            Err(ParseError::InvalidNumber {
+                index: 1,
+                offset: 5,
+                token: "x".to_string()
+            }),
+            to_float_arr::<f64>("0.1, x")
+        );
+    }
+
+    #[test]
+    fn parse_floats_tolerates_separators_and_notation() {
+        assert_eq!(Ok([1.0, 0.0, 0.0]), parse_floats::<3>("1, 0, 0"));
+        assert_eq!(Ok([1.0, 0.0, 0.0]), parse_floats::<3>(" 1;0 ;\t0 "));
+        assert_eq!(Ok([1.0, 2.0, 3.0]), parse_floats::<3>("1 2 3"));
+        assert_eq!(
+            Ok([0.0, 1.799_372_4e-29, 0.03]),
+            parse_floats::<3>("0,1.799372463566829e-029,3E-2")
+        );
+        assert_eq!(Ok([]), parse_floats::<0>(" "));
+        assert_eq!(Ok([0.1f64, 1e300]), parse_doubles::<2>("0.1; 1e300"));
+    }
+
+    #[test]
+    fn parse_floats_reports_bad_token() {
+        assert_eq!(
+            Err(ParseError::InvalidNumber {
+                index: 1,
+                offset: 3,
+                token: "x".to_string()
+            }),
+            parse_floats::<3>("1, x, 0")
+        );
+        assert_eq!(
+            "Invalid number '' for value 1 at offset 2",
+            parse_floats::<3>("1,,0").unwrap_err().to_string()
+        );
+        assert_eq!(
+            "Invalid number 'x' for value 3 at offset 6",
+            parse_floats::<3>("1,0,0,x").unwrap_err().to_string()
+        );
+        assert_eq!(
+            "Invalid number '' for value 3 at offset 6",
+            parse_floats::<3>("1,2,3,").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn parse_floats_rejects_non_finite_values() {
+        assert_eq!(
+            Err(ParseError::InvalidNumber {
+                index: 0,
+                offset: 0,
+                token: "nan".to_string()
+            }),
+            parse_floats::<3>("nan,inf,1")
+        );
+        assert_eq!(
+            "Invalid number 'infinity' for value 1 at offset 3",
+            parse_floats::<3>("1, infinity, 0").unwrap_err().to_string()
+        );
+        assert!(parse_floats::<1>("1e39").is_err());
+        assert_eq!(Ok([1e39]), parse_doubles::<1>("1e39"));
+        assert!(to_float_arr::<f64>("0, -inf").is_err());
+    }
+
+    #[test]
+    fn parse_floats_checks_count() {
+        assert_eq!(
+            Err(ParseError::WrongCount {
+                expected: 3,
+                actual: 2
+            }),
+            parse_floats::<3>("1, 0")
+        );
+        assert_eq!(
+            "Expected 3 values but found 4",
+            parse_floats::<3>("1,0,0,1").unwrap_err().to_string()
+        );
+        assert_eq!(
+            Err(ParseError::WrongCount {
+                expected: 1,
+                actual: 0
+            }),
+            parse_floats::<1>("")
+        );
+    }
 }
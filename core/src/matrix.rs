@@ -0,0 +1,246 @@
+use std::ops::Mul;
+
+use crate::{
+    approx_eq_4x4, is_identity_within, is_orthonormal, multiply_4x4, to_transform, Matrix4x4,
+    Transform, Vector3f, Vector4f,
+};
+
+// Allowance for rounding in decompose, where scales this close to zero leave no rotation and
+// columns this far from orthonormal mean the matrix is sheared.
+const DECOMPOSE_EPSILON: f32 = 1e-5;
+
+// Row-major affine transform with the translation in the last column, the same layout as
+// `Transform` and the raw arrays used elsewhere in this crate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix4(pub [[f32; 4]; 4]);
+
+// Translation, rotation and scale that compose back into a matrix as T * R * S.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decomposition {
+    pub translation: Vector3f,
+    pub rotation: [[f32; 3]; 3],
+    pub scale: Vector3f,
+}
+
+impl Matrix4 {
+    pub const IDENTITY: Matrix4 = Matrix4(Matrix4x4::IDENTITY);
+
+    pub fn from_translation(t: Vector3f) -> Matrix4 {
+        let mut m = Matrix4::IDENTITY;
+        m.0[0][3] = t.x;
+        m.0[1][3] = t.y;
+        m.0[2][3] = t.z;
+        m
+    }
+
+    pub fn from_scale(s: Vector3f) -> Matrix4 {
+        let mut m = Matrix4::IDENTITY;
+        m.0[0][0] = s.x;
+        m.0[1][1] = s.y;
+        m.0[2][2] = s.z;
+        m
+    }
+
+    pub fn from_rotation(r: [[f32; 3]; 3]) -> Matrix4 {
+        let mut m = Matrix4::IDENTITY;
+        for (row, r_row) in m.0.iter_mut().zip(r.iter()) {
+            row[..3].copy_from_slice(r_row);
+        }
+        m
+    }
+
+    pub fn from_trs(d: &Decomposition) -> Matrix4 {
+        Matrix4::from_translation(d.translation)
+            * Matrix4::from_rotation(d.rotation)
+            * Matrix4::from_scale(d.scale)
+    }
+
+    pub fn approx_eq(&self, other: &Matrix4, epsilon: f32) -> bool {
+        approx_eq_4x4(self.0, other.0, epsilon)
+    }
+
+    pub fn is_identity_within(&self, epsilon: f32) -> bool {
+        is_identity_within(self.0, epsilon)
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut m = Matrix4(Matrix4x4::ZERO);
+        for r in 0..4 {
+            for c in 0..4 {
+                m.0[c][r] = self.0[r][c];
+            }
+        }
+        m
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let (s, c) = self.minors();
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+
+    // None when the matrix is singular.
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let m = &self.0;
+        let (s, c) = self.minors();
+        let det = s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0];
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+
+        let adjugate = [
+            [
+                m[1][1] * c[5] - m[1][2] * c[4] + m[1][3] * c[3],
+                -m[0][1] * c[5] + m[0][2] * c[4] - m[0][3] * c[3],
+                m[3][1] * s[5] - m[3][2] * s[4] + m[3][3] * s[3],
+                -m[2][1] * s[5] + m[2][2] * s[4] - m[2][3] * s[3],
+            ],
+            [
+                -m[1][0] * c[5] + m[1][2] * c[2] - m[1][3] * c[1],
+                m[0][0] * c[5] - m[0][2] * c[2] + m[0][3] * c[1],
+                -m[3][0] * s[5] + m[3][2] * s[2] - m[3][3] * s[1],
+                m[2][0] * s[5] - m[2][2] * s[2] + m[2][3] * s[1],
+            ],
+            [
+                m[1][0] * c[4] - m[1][1] * c[2] + m[1][3] * c[0],
+                -m[0][0] * c[4] + m[0][1] * c[2] - m[0][3] * c[0],
+                m[3][0] * s[4] - m[3][1] * s[2] + m[3][3] * s[0],
+                -m[2][0] * s[4] + m[2][1] * s[2] - m[2][3] * s[0],
+            ],
+            [
+                -m[1][0] * c[3] + m[1][1] * c[1] - m[1][2] * c[0],
+                m[0][0] * c[3] - m[0][1] * c[1] + m[0][2] * c[0],
+                -m[3][0] * s[3] + m[3][1] * s[1] - m[3][2] * s[0],
+                m[2][0] * s[3] - m[2][1] * s[1] + m[2][2] * s[0],
+            ],
+        ];
+
+        Some(Matrix4(adjugate.map(|row| row.map(|v| v / det))))
+    }
+
+    // 2x2 minors of the top two rows (s) and bottom two rows (c), shared by the determinant
+    // and the adjugate.
+    fn minors(&self) -> ([f32; 6], [f32; 6]) {
+        let m = &self.0;
+        let s = [
+            m[0][0] * m[1][1] - m[1][0] * m[0][1],
+            m[0][0] * m[1][2] - m[1][0] * m[0][2],
+            m[0][0] * m[1][3] - m[1][0] * m[0][3],
+            m[0][1] * m[1][2] - m[1][1] * m[0][2],
+            m[0][1] * m[1][3] - m[1][1] * m[0][3],
+            m[0][2] * m[1][3] - m[1][2] * m[0][3],
+        ];
+        let c = [
+            m[2][0] * m[3][1] - m[3][0] * m[2][1],
+            m[2][0] * m[3][2] - m[3][0] * m[2][2],
+            m[2][0] * m[3][3] - m[3][0] * m[2][3],
+            m[2][1] * m[3][2] - m[3][1] * m[2][2],
+            m[2][1] * m[3][3] - m[3][1] * m[2][3],
+            m[2][2] * m[3][3] - m[3][2] * m[2][3],
+        ];
+        (s, c)
+    }
+
+    pub fn transform_point(&self, p: Vector3f) -> Vector3f {
+        let m = &self.0;
+        Vector3f::new(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+            m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3],
+        )
+    }
+
+    // Like transform_point but ignores the translation, for directions and offsets.
+    pub fn transform_vector(&self, v: Vector3f) -> Vector3f {
+        let m = &self.0;
+        Vector3f::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+
+    // Splits an affine matrix into translation, rotation and scale. Mirroring shows up as a
+    // negative x scale. None when a scale is zero or the matrix is sheared, since neither
+    // leaves a rotation to recover.
+    pub fn decompose(&self) -> Option<Decomposition> {
+        let m = &self.0;
+        let column = |c: usize| Vector3f::new(m[0][c], m[1][c], m[2][c]);
+        let length = |v: Vector3f| (v.x * v.x + v.y * v.y + v.z * v.z).sqrt();
+
+        let mut scale = Vector3f::new(length(column(0)), length(column(1)), length(column(2)));
+        if self.determinant() < 0.0 {
+            scale.x = -scale.x;
+        }
+        if [scale.x, scale.y, scale.z]
+            .iter()
+            .any(|s| s.abs() <= DECOMPOSE_EPSILON)
+        {
+            return None;
+        }
+
+        let divisors = [scale.x, scale.y, scale.z];
+        let mut rotation = [[0.0; 3]; 3];
+        for (r, row) in rotation.iter_mut().enumerate() {
+            for (c, v) in row.iter_mut().enumerate() {
+                *v = m[r][c] / divisors[c];
+            }
+        }
+
+        if !is_orthonormal(&rotation, DECOMPOSE_EPSILON) {
+            return None;
+        }
+
+        Some(Decomposition {
+            translation: column(3),
+            rotation,
+            scale,
+        })
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, rhs: Matrix4) -> Matrix4 {
+        Matrix4(multiply_4x4(self.0, rhs.0))
+    }
+}
+
+impl Mul<Vector3f> for Matrix4 {
+    type Output = Vector3f;
+
+    fn mul(self, rhs: Vector3f) -> Vector3f {
+        self.transform_point(rhs)
+    }
+}
+
+impl From<[[f32; 4]; 4]> for Matrix4 {
+    fn from(m: [[f32; 4]; 4]) -> Self {
+        Matrix4(m)
+    }
+}
+
+impl From<Matrix4> for [[f32; 4]; 4] {
+    fn from(m: Matrix4) -> Self {
+        m.0
+    }
+}
+
+impl From<&Transform> for Matrix4 {
+    fn from(t: &Transform) -> Self {
+        let row = |v: &Vector4f| [v.x, v.y, v.z, v.w];
+        Matrix4([row(&t.r0), row(&t.r1), row(&t.r2), row(&t.r3)])
+    }
+}
+
+impl From<Transform> for Matrix4 {
+    fn from(t: Transform) -> Self {
+        Matrix4::from(&t)
+    }
+}
+
+impl From<Matrix4> for Transform {
+    fn from(m: Matrix4) -> Self {
+        to_transform(m.0)
+    }
+}
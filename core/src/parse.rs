@@ -0,0 +1,120 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    // `index` counts values from zero and `offset` is the token's byte offset in the input.
+    InvalidNumber {
+        index: usize,
+        offset: usize,
+        token: String,
+    },
+    WrongCount {
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::InvalidNumber {
+                index,
+                offset,
+                token,
+            } => write!(
+                f,
+                "Invalid number '{}' for value {} at offset {}",
+                token, index, offset
+            ),
+            ParseError::WrongCount { expected, actual } => {
+                write!(f, "Expected {} values but found {}", expected, actual)
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+// Number types the parsers accept. Values that parse but are not finite, like "NaN", "inf" or
+// an out of range "1e39" as f32, are rejected as invalid numbers.
+pub trait Float: Copy + Default + FromStr {
+    fn is_finite(self) -> bool;
+}
+
+impl Float for f32 {
+    fn is_finite(self) -> bool {
+        f32::is_finite(self)
+    }
+}
+
+impl Float for f64 {
+    fn is_finite(self) -> bool {
+        f64::is_finite(self)
+    }
+}
+
+// Parses exactly N numbers separated by commas, semicolons or whitespace, e.g. "1, 0;2.5e-3".
+pub fn parse_floats<const N: usize>(s: &str) -> Result<[f32; N], ParseError> {
+    parse_array(s)
+}
+
+pub fn parse_doubles<const N: usize>(s: &str) -> Result<[f64; N], ParseError> {
+    parse_array(s)
+}
+
+// Like parse_floats but for any number of values.
+pub fn to_float_arr<T: Float>(s: &str) -> Result<Vec<T>, ParseError> {
+    tokens(s)
+        .enumerate()
+        .map(|(index, token)| parse_token(s, index, token))
+        .collect()
+}
+
+// Every token is parsed, extra ones included, so an invalid token is reported with its position
+// before a wrong count.
+fn parse_array<T: Float, const N: usize>(s: &str) -> Result<[T; N], ParseError> {
+    let mut values = [T::default(); N];
+    let mut count = 0;
+    for token in tokens(s) {
+        let value = parse_token(s, count, token)?;
+        if count < N {
+            values[count] = value;
+        }
+        count += 1;
+    }
+
+    if count == N {
+        Ok(values)
+    } else {
+        Err(ParseError::WrongCount {
+            expected: N,
+            actual: count,
+        })
+    }
+}
+
+fn parse_token<T: Float>(s: &str, index: usize, token: &str) -> Result<T, ParseError> {
+    match token.parse::<T>() {
+        Ok(value) if value.is_finite() => Ok(value),
+        _ => Err(ParseError::InvalidNumber {
+            index,
+            offset: token.as_ptr() as usize - s.as_ptr() as usize,
+            token: token.to_string(),
+        }),
+    }
+}
+
+// Whitespace around a comma or semicolon belongs to it, but two of them in a row leave an
+// empty token that fails to parse rather than being skipped.
+fn tokens(s: &str) -> impl Iterator<Item = &str> {
+    let blank = s.trim().is_empty();
+    s.split([',', ';'])
+        .filter(move |_| !blank)
+        .flat_map(|field| {
+            let trimmed = field.trim();
+            let empty = trimmed.is_empty().then_some(trimmed);
+            trimmed.split_whitespace().chain(empty)
+        })
+}
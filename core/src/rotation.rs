@@ -0,0 +1,309 @@
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::ops::Mul;
+
+use crate::Vector3f;
+
+// Sine of the middle Euler angle beyond which the first and last axes line up.
+const GIMBAL_LOCK: f32 = 1.0 - 1e-6;
+
+// Row-major rotation matrix, the same layout as `Decomposition::rotation`.
+pub type Rotation3 = [[f32; 3]; 3];
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Quaternion {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+// Order in which Euler angles are applied, as intrinsic rotations about the rotated axes.
+// `Xyz` is R = Rx * Ry * Rz and `Zyx`, the yaw-pitch-roll convention, is R = Rz * Ry * Rx.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EulerOrder {
+    Xyz,
+    Zyx,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RotationError {
+    NotOrthonormal { error: f32 },
+    Reflection,
+}
+
+impl fmt::Display for RotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RotationError::NotOrthonormal { error } => {
+                write!(f, "Matrix is not orthonormal (off by {})", error)
+            }
+            RotationError::Reflection => write!(f, "Matrix is a reflection, not a rotation"),
+        }
+    }
+}
+
+impl Error for RotationError {}
+
+impl Quaternion {
+    pub const IDENTITY: Quaternion = Quaternion {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 1.0,
+    };
+
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Quaternion {
+        Quaternion { x, y, z, w }
+    }
+
+    // Angle in radians, counterclockwise about the axis.
+    pub fn from_axis_angle(axis: Vector3f, angle: f32) -> Quaternion {
+        let length = (axis.x * axis.x + axis.y * axis.y + axis.z * axis.z).sqrt();
+        if length == 0.0 {
+            return Quaternion::IDENTITY;
+        }
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        let s = sin / length;
+        Quaternion::new(axis.x * s, axis.y * s, axis.z * s, cos)
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    pub fn length(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    // A zero quaternion has no direction to keep and normalizes to the identity.
+    pub fn normalize(&self) -> Quaternion {
+        let length = self.length();
+        if length == 0.0 || !length.is_finite() {
+            return Quaternion::IDENTITY;
+        }
+        Quaternion::new(
+            self.x / length,
+            self.y / length,
+            self.z / length,
+            self.w / length,
+        )
+    }
+
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion::new(-self.x, -self.y, -self.z, self.w)
+    }
+
+    // Assumes a rotation matrix, use try_from_rotation to check it first.
+    pub fn from_rotation(m: &Rotation3) -> Quaternion {
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion::new(
+                (m[2][1] - m[1][2]) / s,
+                (m[0][2] - m[2][0]) / s,
+                (m[1][0] - m[0][1]) / s,
+                s / 4.0,
+            )
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.0;
+            Quaternion::new(
+                s / 4.0,
+                (m[0][1] + m[1][0]) / s,
+                (m[0][2] + m[2][0]) / s,
+                (m[2][1] - m[1][2]) / s,
+            )
+        } else if m[1][1] > m[2][2] {
+            let s = (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.0;
+            Quaternion::new(
+                (m[0][1] + m[1][0]) / s,
+                s / 4.0,
+                (m[1][2] + m[2][1]) / s,
+                (m[0][2] - m[2][0]) / s,
+            )
+        } else {
+            let s = (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.0;
+            Quaternion::new(
+                (m[0][2] + m[2][0]) / s,
+                (m[1][2] + m[2][1]) / s,
+                s / 4.0,
+                (m[1][0] - m[0][1]) / s,
+            )
+        };
+
+        q.normalize()
+    }
+
+    pub fn try_from_rotation(m: &Rotation3, epsilon: f32) -> Result<Quaternion, RotationError> {
+        validate_rotation(m, epsilon)?;
+        Ok(Quaternion::from_rotation(m))
+    }
+
+    pub fn to_rotation(&self) -> Rotation3 {
+        let Quaternion { x, y, z, w } = self.normalize();
+        [
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - z * w),
+                2.0 * (x * z + y * w),
+            ],
+            [
+                2.0 * (x * y + z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - x * w),
+            ],
+            [
+                2.0 * (x * z - y * w),
+                2.0 * (y * z + x * w),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ]
+    }
+
+    // Angles in radians about x, y and z, whatever the order they are applied in.
+    pub fn from_euler(angles: Vector3f, order: EulerOrder) -> Quaternion {
+        let x = Quaternion::from_axis_angle(Vector3f::new(1.0, 0.0, 0.0), angles.x);
+        let y = Quaternion::from_axis_angle(Vector3f::new(0.0, 1.0, 0.0), angles.y);
+        let z = Quaternion::from_axis_angle(Vector3f::new(0.0, 0.0, 1.0), angles.z);
+        match order {
+            EulerOrder::Xyz => x * y * z,
+            EulerOrder::Zyx => z * y * x,
+        }
+    }
+
+    // At gimbal lock the last rotation is folded into the first and reported as zero.
+    pub fn to_euler(&self, order: EulerOrder) -> Vector3f {
+        let m = self.to_rotation();
+        match order {
+            EulerOrder::Xyz => {
+                let y = m[0][2].clamp(-1.0, 1.0).asin();
+                if m[0][2].abs() < GIMBAL_LOCK {
+                    Vector3f::new((-m[1][2]).atan2(m[2][2]), y, (-m[0][1]).atan2(m[0][0]))
+                } else {
+                    Vector3f::new(m[2][1].atan2(m[1][1]), y, 0.0)
+                }
+            }
+            EulerOrder::Zyx => {
+                let y = (-m[2][0]).clamp(-1.0, 1.0).asin();
+                if m[2][0].abs() < GIMBAL_LOCK {
+                    Vector3f::new(m[2][1].atan2(m[2][2]), y, m[1][0].atan2(m[0][0]))
+                } else {
+                    Vector3f::new(0.0, y, (-m[0][1]).atan2(m[1][1]))
+                }
+            }
+        }
+    }
+
+    // Spherical interpolation along the shorter arc, t between 0 and 1.
+    pub fn slerp(&self, other: &Quaternion, t: f32) -> Quaternion {
+        let (a, mut b) = (self.normalize(), other.normalize());
+        let mut cos = a.dot(&b);
+        if cos < 0.0 {
+            b = Quaternion::new(-b.x, -b.y, -b.z, -b.w);
+            cos = -cos;
+        }
+
+        // Nearly parallel quaternions divide by a vanishing sine, so interpolate linearly.
+        let (wa, wb) = if cos > 1.0 - 1e-6 {
+            (1.0 - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        Quaternion::new(
+            wa * a.x + wb * b.x,
+            wa * a.y + wb * b.y,
+            wa * a.z + wb * b.z,
+            wa * a.w + wb * b.w,
+        )
+        .normalize()
+    }
+}
+
+// Hamilton product, so (a * b) applies b first and then a.
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, r: Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w * r.x + self.x * r.w + self.y * r.z - self.z * r.y,
+            self.w * r.y - self.x * r.z + self.y * r.w + self.z * r.x,
+            self.w * r.z + self.x * r.y - self.y * r.x + self.z * r.w,
+            self.w * r.w - self.x * r.x - self.y * r.y - self.z * r.z,
+        )
+    }
+}
+
+pub fn determinant_3x3(m: &Rotation3) -> f32 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+// Largest deviation of M^T * M from the identity, zero for an exactly orthonormal matrix and
+// infinite when an entry is NaN or infinite, which f32::max would otherwise skip.
+pub fn orthonormality_error(m: &Rotation3) -> f32 {
+    let mut error: f32 = 0.0;
+    for i in 0..3 {
+        for j in 0..3 {
+            let dot: f32 = (0..3).map(|k| m[k][i] * m[k][j]).sum();
+            if !dot.is_finite() {
+                return f32::INFINITY;
+            }
+            let expected = if i == j { 1.0 } else { 0.0 };
+            error = error.max((dot - expected).abs());
+        }
+    }
+    error
+}
+
+pub fn is_orthonormal(m: &Rotation3, epsilon: f32) -> bool {
+    orthonormality_error(m) <= epsilon
+}
+
+// Orthonormal matrix from Gram-Schmidt on the columns, which keeps the first column's direction
+// and the handedness but is not the nearest one. None when the columns are degenerate.
+pub fn orthonormalize(m: &Rotation3) -> Option<Rotation3> {
+    let column = |c: usize| [m[0][c], m[1][c], m[2][c]];
+    let dot = |a: [f32; 3], b: [f32; 3]| a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+    let normalized = |a: [f32; 3]| {
+        let length = dot(a, a).sqrt();
+        if length <= f32::EPSILON || !length.is_finite() {
+            None
+        } else {
+            Some(a.map(|v| v / length))
+        }
+    };
+    let cross = |a: [f32; 3], b: [f32; 3]| {
+        [
+            a[1] * b[2] - a[2] * b[1],
+            a[2] * b[0] - a[0] * b[2],
+            a[0] * b[1] - a[1] * b[0],
+        ]
+    };
+
+    let x = normalized(column(0))?;
+    let y = column(1);
+    let d = dot(x, y);
+    let y = normalized([y[0] - d * x[0], y[1] - d * x[1], y[2] - d * x[2]])?;
+    let mut z = cross(x, y);
+    if dot(z, column(2)) < 0.0 {
+        z = z.map(|v| -v);
+    }
+
+    Some([[x[0], y[0], z[0]], [x[1], y[1], z[1]], [x[2], y[2], z[2]]])
+}
+
+// Orthonormal columns with a positive determinant, i.e. a proper rotation.
+pub fn validate_rotation(m: &Rotation3, epsilon: f32) -> Result<(), RotationError> {
+    let error = orthonormality_error(m);
+    if error > epsilon {
+        return Err(RotationError::NotOrthonormal { error });
+    }
+    if determinant_3x3(m) < 0.0 {
+        return Err(RotationError::Reflection);
+    }
+    Ok(())
+}
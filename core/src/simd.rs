@@ -0,0 +1,74 @@
+// SSE matrix products. Every output row is accumulated as 0 + x0*y0 + x1*y1 + x2*y2 + x3*y3 with
+// separate multiplies and adds, the same operations in the same order as the scalar loop, so
+// both give bit-identical results.
+use std::arch::x86_64::{
+    __m128, _mm_add_ps, _mm_loadu_ps, _mm_mul_ps, _mm_set1_ps, _mm_setzero_ps, _mm_storeu_ps,
+};
+
+#[inline]
+pub fn multiply_4x4(xs: [[f32; 4]; 4], ys: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
+    let ys = load(&ys);
+    let mut result = [[0.0; 4]; 4];
+    for (out, x) in result.iter_mut().zip(xs.iter()) {
+        store(out, row(&broadcast(x), &ys));
+    }
+
+    result
+}
+
+// The parent's broadcast entries are shared by every child, so they are set up only once.
+pub fn multiply_4x4_batch(parent: [[f32; 4]; 4], children: &mut [[[f32; 4]; 4]]) {
+    let xs = [
+        broadcast(&parent[0]),
+        broadcast(&parent[1]),
+        broadcast(&parent[2]),
+        broadcast(&parent[3]),
+    ];
+    for child in children.iter_mut() {
+        let ys = load(child);
+        for (out, x) in child.iter_mut().zip(xs.iter()) {
+            store(out, row(x, &ys));
+        }
+    }
+}
+
+// SSE is part of the x86_64 baseline, so these intrinsics are always available.
+#[inline(always)]
+fn row(x: &[__m128; 4], ys: &[__m128; 4]) -> __m128 {
+    unsafe {
+        let mut acc = _mm_setzero_ps();
+        acc = _mm_add_ps(acc, _mm_mul_ps(x[0], ys[0]));
+        acc = _mm_add_ps(acc, _mm_mul_ps(x[1], ys[1]));
+        acc = _mm_add_ps(acc, _mm_mul_ps(x[2], ys[2]));
+        _mm_add_ps(acc, _mm_mul_ps(x[3], ys[3]))
+    }
+}
+
+#[inline(always)]
+fn broadcast(x: &[f32; 4]) -> [__m128; 4] {
+    unsafe {
+        [
+            _mm_set1_ps(x[0]),
+            _mm_set1_ps(x[1]),
+            _mm_set1_ps(x[2]),
+            _mm_set1_ps(x[3]),
+        ]
+    }
+}
+
+#[inline(always)]
+fn load(m: &[[f32; 4]; 4]) -> [__m128; 4] {
+    unsafe {
+        [
+            _mm_loadu_ps(m[0].as_ptr()),
+            _mm_loadu_ps(m[1].as_ptr()),
+            _mm_loadu_ps(m[2].as_ptr()),
+            _mm_loadu_ps(m[3].as_ptr()),
+        ]
+    }
+}
+
+#[inline(always)]
+fn store(out: &mut [f32; 4], v: __m128) {
+    unsafe { _mm_storeu_ps(out.as_mut_ptr(), v) }
+}
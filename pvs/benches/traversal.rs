@@ -0,0 +1,62 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const LEVELS: usize = 300;
+const LEAVES_PER_LEVEL: usize = 50;
+
+// Each level instances the level below it plus a fan of leaf parts, so the result is both deep
+// and wide: LEVELS * (LEAVES_PER_LEVEL + 1) + 1 scene items.
+fn synthetic_pvs() -> String {
+    let mut xml = String::from(
+        r#"<?xml version="1.0"?>
+<PV_FILE type="PVS" version="0301">
+<section_structure>
+  <component name="Leaf">
+    <shape_source file_name="leaf.ol" bbox="-0.01,-0.01,-0.01,0.01,0.01,0.01"/>
+  </component>
+  <component name="Level 0">
+    <component_instance index="0" id="0"/>
+  </component>
+"#,
+    );
+    for level in 1..=LEVELS {
+        xml.push_str(&format!("  <component name=\"Level {}\">\n", level));
+        xml.push_str(&format!(
+            "    <component_instance index=\"{}\" translation=\"0,0,0.01\" orientation=\"0,1,0,-1,0,0,0,0,1\" id=\"a{}\"/>\n",
+            level, level
+        ));
+        for leaf in 0..LEAVES_PER_LEVEL {
+            xml.push_str(&format!(
+                "    <component_instance index=\"0\" translation=\"{},0,0\" id=\"{}\"/>\n",
+                leaf as f32 * 0.02,
+                leaf
+            ));
+        }
+        xml.push_str("  </component>\n");
+    }
+    xml.push_str("</section_structure>\n</PV_FILE>\n");
+    xml
+}
+
+fn write_synthetic_pvs() -> PathBuf {
+    let path = env::temp_dir().join("pvs-traversal-bench.xml");
+    fs::write(&path, synthetic_pvs()).expect("Error writing synthetic PVS");
+    path
+}
+
+fn traversal(c: &mut Criterion) {
+    let path = write_synthetic_pvs();
+    let src = path.to_str().unwrap();
+    let count = pvs::run(src).unwrap().len();
+
+    let mut group = c.benchmark_group("traversal");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(count as u64));
+    group.bench_function("deep_and_wide", |b| b.iter(|| pvs::run(src).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, traversal);
+criterion_main!(benches);
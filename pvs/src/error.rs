@@ -0,0 +1,162 @@
+use quick_xml::DeError;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum PvsError {
+    Io(io::Error),
+    Xml(DeError),
+    EmptyStructure,
+    NoRoot,
+    AmbiguousRoot {
+        candidates: Vec<(usize, String)>,
+    },
+    RootNotFound {
+        root: String,
+    },
+    InvalidIndex {
+        component: String,
+        instance_id: String,
+        index: String,
+    },
+    InvalidArity {
+        component: String,
+        instance_id: String,
+        attribute: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+    // `index` counts values from zero and `offset` is the token's byte offset in `value`. The
+    // token is a boxed str to keep PvsError small.
+    InvalidNumber {
+        component: String,
+        instance_id: String,
+        attribute: &'static str,
+        value: String,
+        index: usize,
+        offset: usize,
+        token: Box<str>,
+    },
+    InvalidOrientation {
+        component: String,
+        instance_id: String,
+        determinant: f32,
+        error: f32,
+    },
+    InvalidPropertyIndex {
+        index: String,
+    },
+    Cycle {
+        path: String,
+        index: usize,
+    },
+    MaxDepthExceeded {
+        path: String,
+        max_depth: usize,
+    },
+}
+
+impl fmt::Display for PvsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PvsError::Io(e) => write!(f, "I/O error: {}", e),
+            PvsError::Xml(e) => write!(f, "XML error: {}", e),
+            PvsError::EmptyStructure => write!(f, "PVS file has no components"),
+            PvsError::NoRoot => write!(
+                f,
+                "PVS file has no root, every component is referenced by an instance"
+            ),
+            PvsError::AmbiguousRoot { candidates } => {
+                let names: Vec<String> = candidates
+                    .iter()
+                    .map(|(idx, name)| format!("{} '{}'", idx, name))
+                    .collect();
+                write!(
+                    f,
+                    "PVS file has several candidate roots ({}), choose one by index or name",
+                    names.join(", ")
+                )
+            }
+            PvsError::RootNotFound { root } => write!(f, "Root component {} not found", root),
+            PvsError::InvalidIndex {
+                component,
+                instance_id,
+                index,
+            } => write!(
+                f,
+                "Instance '{}' in component '{}' has invalid index '{}'",
+                instance_id, component, index
+            ),
+            PvsError::InvalidArity {
+                component,
+                instance_id,
+                attribute,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Instance '{}' in component '{}' has {} {} values but expected {}",
+                instance_id, component, actual, attribute, expected
+            ),
+            PvsError::InvalidNumber {
+                component,
+                instance_id,
+                attribute,
+                value,
+                index,
+                offset,
+                token,
+            } => write!(
+                f,
+                "Instance '{}' in component '{}' has invalid {} '{}' (value {} '{}' at offset {})",
+                instance_id, component, attribute, value, index, token, offset
+            ),
+            PvsError::InvalidOrientation {
+                component,
+                instance_id,
+                determinant,
+                error,
+            } => write!(
+                f,
+                "Instance '{}' in component '{}' has a non-orthonormal orientation (determinant {}, off by {})",
+                instance_id, component, determinant, error
+            ),
+            PvsError::InvalidPropertyIndex { index } => {
+                write!(f, "Property component ref has invalid index '{}'", index)
+            }
+            PvsError::Cycle { path, index } => {
+                write!(f, "Cyclic component reference {} -> index {}", path, index)
+            }
+            PvsError::MaxDepthExceeded { path, max_depth } => {
+                write!(
+                    f,
+                    "Instance {} exceeds maximum depth of {}",
+                    path, max_depth
+                )
+            }
+        }
+    }
+}
+
+impl Error for PvsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PvsError::Io(e) => Some(e),
+            PvsError::Xml(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PvsError {
+    fn from(e: io::Error) -> Self {
+        PvsError::Io(e)
+    }
+}
+
+impl From<DeError> for PvsError {
+    fn from(e: DeError) -> Self {
+        PvsError::Xml(e)
+    }
+}
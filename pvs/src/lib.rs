@@ -1,18 +1,275 @@
 extern crate quick_xml;
 
+use log::{debug, info, warn};
 use quick_xml::de;
 use serde::{Deserialize, Serialize};
-use std::error::Error;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::fs::File;
-use std::io::BufReader;
-use std::path::Path;
+use std::io::{BufRead, BufReader};
+use std::ops::Mul;
+use std::str::FromStr;
 
-use core::{self, ColorMaterial, Transform};
+use core::{self, BoundingBox3, ColorMaterial, ParseError, Transform};
+
+mod error;
+mod material;
+
+pub use error::PvsError;
 
 const DEFAULT_ORIENTATION: &str = "1,0,0,0,1,0,0,0,1";
 const DEFAULT_SUPPLIED_REVISION_ID: &str = "1";
 const DEFAULT_TRANSLATION: &str = "0,0,0";
+// Exports round orientations to a handful of digits, so allow for more than float noise.
+const ORIENTATION_TOLERANCE: f32 = 1e-3;
 const PATH_ID_SEPARATOR: &str = "/";
+const REVISION_PROPERTY: &str = "revId";
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum PropertyFilter {
+    #[default]
+    All,
+    Allow(BTreeSet<String>),
+    Deny(BTreeSet<String>),
+}
+
+impl PropertyFilter {
+    pub fn includes(&self, name: &str) -> bool {
+        match self {
+            PropertyFilter::All => true,
+            PropertyFilter::Allow(names) => names.contains(name),
+            PropertyFilter::Deny(names) => !names.contains(name),
+        }
+    }
+}
+
+// How instances flagged with `hide_self` or `hide_child` are converted. `hide_self` hides only
+// the instance itself while `hide_child` hides everything below it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum HiddenInstances {
+    // Drop flagged instances along with their whole subtree.
+    #[default]
+    Skip,
+    // Emit every instance and mark the hidden ones with `visible: false`.
+    Keep,
+    // Drop only the hidden items, attaching visible children to their nearest visible ancestor.
+    Prune,
+}
+
+impl FromStr for HiddenInstances {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(HiddenInstances::Skip),
+            "keep" => Ok(HiddenInstances::Keep),
+            "prune" => Ok(HiddenInstances::Prune),
+            _ => Err(format!(
+                "Unknown hidden instance handling '{}', expected skip, keep or prune",
+                s
+            )),
+        }
+    }
+}
+
+// Which component the scene is built from.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum RootSelection {
+    // The single component that no instance references.
+    #[default]
+    Detect,
+    // The last component, which is where most exporters put the root.
+    Last,
+    Index(usize),
+    // The component with this name, preferring an unreferenced one when names repeat.
+    Name(String),
+    // Every unreferenced component, each as its own root item.
+    All,
+}
+
+impl fmt::Display for RootSelection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RootSelection::Detect => f.write_str("detect"),
+            RootSelection::Last => f.write_str("last"),
+            RootSelection::Index(idx) => write!(f, "index:{}", idx),
+            RootSelection::Name(name) => write!(f, "name:{}", name),
+            RootSelection::All => f.write_str("all"),
+        }
+    }
+}
+
+impl FromStr for RootSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "detect" => Ok(RootSelection::Detect),
+            "last" => Ok(RootSelection::Last),
+            "all" => Ok(RootSelection::All),
+            _ => {
+                if let Some(idx) = s.strip_prefix("index:") {
+                    idx.parse()
+                        .map(RootSelection::Index)
+                        .map_err(|_| format!("Invalid root index '{}'", idx))
+                } else if let Some(name) = s.strip_prefix("name:") {
+                    Ok(RootSelection::Name(name.to_string()))
+                } else {
+                    Err(format!(
+                        "Unknown root selection '{}', expected detect, last, all, index:N or name:NAME",
+                        s
+                    ))
+                }
+            }
+        }
+    }
+}
+
+// What to do with orientations whose columns are not orthonormal, which skews geometry.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OrientationCheck {
+    Ignore,
+    #[default]
+    Warn,
+    Error,
+    // Replace them with an orthonormal matrix keeping the first column's direction and the
+    // handedness, so mirrored instances stay reflections.
+    Orthonormalize,
+}
+
+impl FromStr for OrientationCheck {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(OrientationCheck::Ignore),
+            "warn" => Ok(OrientationCheck::Warn),
+            "error" => Ok(OrientationCheck::Error),
+            "orthonormalize" => Ok(OrientationCheck::Orthonormalize),
+            _ => Err(format!(
+                "Unknown orientation check '{}', expected ignore, warn, error or orthonormalize",
+                s
+            )),
+        }
+    }
+}
+
+// Precision world transforms are accumulated in. Items always carry f32 transforms, but double
+// precision keeps millimeters intact for deep hierarchies placed far from the origin.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Precision {
+    #[default]
+    Single,
+    Double,
+}
+
+impl FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "single" => Ok(Precision::Single),
+            "double" => Ok(Precision::Double),
+            _ => Err(format!(
+                "Unknown precision '{}', expected single or double",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LengthUnit {
+    Meter,
+    Centimeter,
+    Millimeter,
+    Inch,
+    Foot,
+}
+
+impl LengthUnit {
+    pub const ALL: [LengthUnit; 5] = [
+        LengthUnit::Meter,
+        LengthUnit::Centimeter,
+        LengthUnit::Millimeter,
+        LengthUnit::Inch,
+        LengthUnit::Foot,
+    ];
+
+    fn millimeters(self) -> f64 {
+        match self {
+            LengthUnit::Meter => 1000.0,
+            LengthUnit::Centimeter => 10.0,
+            LengthUnit::Millimeter => 1.0,
+            LengthUnit::Inch => 25.4,
+            LengthUnit::Foot => 304.8,
+        }
+    }
+
+    // Factor that converts a length in this unit to the target unit.
+    pub fn scale_to(self, target: LengthUnit) -> f32 {
+        self.scale_to_f64(target) as f32
+    }
+
+    pub fn scale_to_f64(self, target: LengthUnit) -> f64 {
+        self.millimeters() / target.millimeters()
+    }
+}
+
+impl fmt::Display for LengthUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            LengthUnit::Meter => "m",
+            LengthUnit::Centimeter => "cm",
+            LengthUnit::Millimeter => "mm",
+            LengthUnit::Inch => "in",
+            LengthUnit::Foot => "ft",
+        })
+    }
+}
+
+impl FromStr for LengthUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LengthUnit::ALL
+            .iter()
+            .find(|u| u.to_string() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown length unit '{}', expected m, cm, mm, in or ft", s))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConvertOptions {
+    pub hidden: HiddenInstances,
+    pub max_depth: Option<usize>,
+    pub orientations: OrientationCheck,
+    pub precision: Precision,
+    pub properties: PropertyFilter,
+    pub root: RootSelection,
+    // Emitted transforms have values within this of 0, 1 or -1 snapped to them.
+    pub snap_epsilon: Option<f32>,
+    pub source_units: LengthUnit,
+    pub target_units: LengthUnit,
+}
+
+// PVS translations are in meters and scene items have historically been in millimeters.
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        ConvertOptions {
+            hidden: HiddenInstances::default(),
+            max_depth: None,
+            orientations: OrientationCheck::default(),
+            precision: Precision::default(),
+            properties: PropertyFilter::default(),
+            root: RootSelection::default(),
+            snap_epsilon: None,
+            source_units: LengthUnit::Meter,
+            target_units: LengthUnit::Millimeter,
+        }
+    }
+}
 
 #[derive(Debug, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -25,9 +282,17 @@ pub struct Source {
 #[derive(Debug, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SceneItem {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bounding_box: Option<BoundingBox3>,
     pub depth: usize,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub material_override: Option<ColorMaterial>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    // The world transform is a reflection, so renderers need to flip face winding.
+    #[serde(skip_serializing_if = "is_false")]
+    pub mirrored: bool,
+    pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub parent_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -35,73 +300,62 @@ pub struct SceneItem {
     pub supplied_id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub transform: Option<Transform>,
+    #[serde(skip_serializing_if = "is_true")]
+    pub visible: bool,
+}
+
+fn is_true(b: &bool) -> bool {
+    *b
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
 }
 
 impl SceneItem {
     fn new(
-        path_id: &str,
-        part_name: &str,
-        part_revision: &str,
-        filename: Option<&str>,
-        transform: Option<[[f32; 4]; 4]>,
+        supplied_id: String,
+        parent_id: Option<String>,
+        depth: usize,
+        name: String,
+        metadata: BTreeMap<String, String>,
     ) -> SceneItem {
-        let supplied_id = if path_id == core::EMPTY_STR {
-            PATH_ID_SEPARATOR
-        } else {
-            path_id
-        };
-        let mut supplied_id_parts: Vec<&str> = supplied_id.split(PATH_ID_SEPARATOR).collect();
-        supplied_id_parts.remove(supplied_id_parts.len() - 1);
-        let parent_id = if supplied_id == PATH_ID_SEPARATOR {
-            None
-        } else {
-            Some(supplied_id_parts.join(PATH_ID_SEPARATOR))
-        };
-        let path_parts: Vec<&str> = path_id.split(PATH_ID_SEPARATOR).collect();
-
-        return SceneItem {
-            depth: path_parts.len() - 1,
+        SceneItem {
+            bounding_box: None,
+            depth,
             material_override: None,
-            parent_id: parent_id.map(|p| {
-                if p == core::EMPTY_STR {
-                    PATH_ID_SEPARATOR.to_string()
-                } else {
-                    p
-                }
-            }),
-            source: filename.map(|f| Source {
-                file_name: f.to_string(),
-                supplied_part_id: part_name.to_string(),
-                supplied_revision_id: part_revision.to_string(),
-            }),
-            supplied_id: supplied_id.to_string(),
-            transform: match transform {
-                Some(t) => {
-                    if !core::is_4x4_identity(t) {
-                        Some(core::to_transform(t))
-                    } else {
-                        None
-                    }
-                }
-                None => None,
-            },
-        };
+            metadata,
+            mirrored: false,
+            name,
+            parent_id,
+            source: None,
+            supplied_id,
+            transform: None,
+            visible: true,
+        }
     }
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 struct ShapeSource {
+    bbox: Option<String>,
     file_name: String,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 struct ComponentInstance {
+    ambient_color: Option<String>,
+    diffuse_color: Option<String>,
+    emissive_color: Option<String>,
     hide_child: Option<bool>,
     hide_self: Option<bool>,
     id: String,
     index: String,
     orientation: Option<String>,
+    shininess: Option<String>,
+    specular_color: Option<String>,
     translation: Option<String>,
+    transparency: Option<String>,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
@@ -126,10 +380,28 @@ struct Property {
 
 #[derive(Debug, Deserialize, PartialEq)]
 struct PropertyComponentRef {
+    index: Option<String>,
     #[serde(rename = "property", default)]
     properties: Vec<Property>,
 }
 
+impl PropertyComponentRef {
+    fn value(&self, name: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| p.value.as_str())
+    }
+
+    fn metadata(&self, filter: &PropertyFilter) -> BTreeMap<String, String> {
+        self.properties
+            .iter()
+            .filter(|p| filter.includes(&p.name))
+            .map(|p| (p.name.clone(), p.value.clone()))
+            .collect()
+    }
+}
+
 #[derive(Debug, Deserialize, PartialEq)]
 struct SectionProperty {
     #[serde(rename = "property_component_ref", default)]
@@ -142,92 +414,798 @@ struct PvFile {
     section_properties: Option<Vec<SectionProperty>>,
 }
 
-pub fn run(src: &str) -> Result<Vec<SceneItem>, Box<dyn Error>> {
-    let pv = parse_from(src)?;
-    let components = &pv.section_structure.components;
-    println!("Found {} components.", components.len());
+impl PvFile {
+    // Refs without an explicit index apply to the component at the same position.
+    fn into_scene(self, options: ConvertOptions) -> Result<Scene, PvsError> {
+        let components = self.section_structure.components;
+        let mut properties: Vec<Option<PropertyComponentRef>> =
+            components.iter().map(|_| None).collect();
+        let refs = self
+            .section_properties
+            .into_iter()
+            .flatten()
+            .flat_map(|sp| sp.property_component_refs);
+        for (pos, pcr) in refs.enumerate() {
+            let idx = match &pcr.index {
+                Some(i) => match i.parse::<usize>() {
+                    Ok(idx) if idx < properties.len() => idx,
+                    _ => return Err(PvsError::InvalidPropertyIndex { index: i.clone() }),
+                },
+                None => pos,
+            };
+            if let Some(p) = properties.get_mut(idx) {
+                *p = Some(pcr);
+            }
+        }
 
-    Ok(create_items(components, components.len() - 1)?)
+        Ok(Scene {
+            components,
+            properties,
+            scale: options.source_units.scale_to_f64(options.target_units),
+            options,
+        })
+    }
 }
 
-fn parse_from<P: AsRef<Path>>(path: P) -> Result<PvFile, Box<dyn Error>> {
-    let pv: PvFile = de::from_reader(BufReader::new(File::open(path)?))?;
-    Ok(pv)
+struct Scene {
+    components: Vec<Component>,
+    properties: Vec<Option<PropertyComponentRef>>,
+    options: ConvertOptions,
+    scale: f64,
 }
 
-fn create_items(
-    components: &Vec<Component>,
-    root_idx: usize,
-) -> Result<Vec<SceneItem>, Box<dyn Error>> {
-    let mut items = vec![];
-    add_items(components, &components[root_idx], "", None, &mut items)?;
-    Ok(items)
+// Streams scene items in depth-first document order without materializing them.
+//
+// Assembly bounds depend on their whole subtree but assemblies are emitted before their
+// children, so a first measuring pass records them. It resolves only transforms and bounding
+// boxes and parses appearances, which surfaces every error before the first item is yielded.
+pub struct Items {
+    scene: Scene,
+    traversal: Traversals,
 }
 
-fn add_items(
-    components: &Vec<Component>,
-    component: &Component,
-    path_id: &str,
-    transform: Option<[[f32; 4]; 4]>,
-    items: &mut Vec<SceneItem>,
-) -> Result<(), Box<dyn Error>> {
-    if component.component_instances.len() > 0 {
-        items.push(SceneItem::new(
-            path_id,
-            &component.name,
-            DEFAULT_SUPPLIED_REVISION_ID,
-            None,
-            None,
-        ));
+enum Traversals {
+    Single(Traversal<f32>),
+    Double(Traversal<f64>),
+}
 
-        for comp_inst in component.component_instances.iter() {
-            if comp_inst.hide_self.unwrap_or(false) || comp_inst.hide_child.unwrap_or(false) {
-                continue;
+impl Items {
+    fn new(pv: PvFile, options: ConvertOptions) -> Result<Items, PvsError> {
+        let scene = pv.into_scene(options)?;
+        let roots = scene.roots()?;
+        for &idx in &roots {
+            debug!(
+                "Using component {} '{}' as root",
+                idx, scene.components[idx].name
+            );
+        }
+
+        let traversal = match scene.options.precision {
+            Precision::Single => Traversals::Single(Traversal::measured(&scene, &roots)?),
+            Precision::Double => Traversals::Double(Traversal::measured(&scene, &roots)?),
+        };
+
+        Ok(Items { scene, traversal })
+    }
+
+    // Hidden instances skipped or marked invisible so far, which is the total once iteration
+    // has finished.
+    pub fn hidden_instances(&self) -> usize {
+        match &self.traversal {
+            Traversals::Single(t) => t.hidden,
+            Traversals::Double(t) => t.hidden,
+        }
+    }
+}
+
+impl Iterator for Items {
+    type Item = Result<SceneItem, PvsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.traversal {
+            Traversals::Single(t) => t.next(&self.scene),
+            Traversals::Double(t) => t.next(&self.scene),
+        }
+    }
+}
+
+pub fn run(src: &str) -> Result<Vec<SceneItem>, PvsError> {
+    run_with(src, &ConvertOptions::default())
+}
+
+pub fn run_with(src: &str, options: &ConvertOptions) -> Result<Vec<SceneItem>, PvsError> {
+    items(src, options)?.collect()
+}
+
+pub fn items(src: &str, options: &ConvertOptions) -> Result<Items, PvsError> {
+    items_from_reader(BufReader::new(File::open(src)?), options)
+}
+
+pub fn items_from_reader<R: BufRead>(
+    reader: R,
+    options: &ConvertOptions,
+) -> Result<Items, PvsError> {
+    let pv: PvFile = de::from_reader(reader)?;
+    info!("Found {} components", pv.section_structure.components.len());
+
+    Items::new(pv, options.clone())
+}
+
+enum Step {
+    Root {
+        component_idx: usize,
+        supplied_id: String,
+    },
+    // Placed instances have their world transform on top of the transform stack.
+    Instance {
+        component_idx: usize,
+        instance_idx: usize,
+        placed: bool,
+    },
+    Exit,
+}
+
+// An assembly whose children are still being traversed. The top of the stack is always the
+// parent of the next instance popped from the step stack.
+struct Open {
+    bounds: Option<BoundingBox3>,
+    component_idx: usize,
+    depth: usize,
+    // Id and depth of the nearest emitted assembly, which differs from this one once pruned.
+    item_depth: usize,
+    item_id: String,
+    ordinal: usize,
+    supplied_id: String,
+    visibility: Visibility,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Visibility {
+    children_hidden: bool,
+    hidden: bool,
+}
+
+// Scalar that world transforms are accumulated in, f32 or f64 depending on `Precision`.
+trait Scalar: Copy + Default + fmt::Debug + From<u8> + Mul<Output = Self> {
+    fn parse_translation(s: &str) -> Result<[Self; 3], ParseError>;
+    fn from_f32(v: f32) -> Self;
+    fn from_f64(v: f64) -> Self;
+    fn multiply_batch(parent: [[Self; 4]; 4], children: &mut [[[Self; 4]; 4]]);
+    fn to_f32_4x4(m: [[Self; 4]; 4]) -> [[f32; 4]; 4];
+}
+
+impl Scalar for f32 {
+    fn parse_translation(s: &str) -> Result<[f32; 3], ParseError> {
+        core::parse_floats(s)
+    }
+
+    fn from_f32(v: f32) -> f32 {
+        v
+    }
+
+    fn from_f64(v: f64) -> f32 {
+        v as f32
+    }
+
+    fn multiply_batch(parent: [[f32; 4]; 4], children: &mut [[[f32; 4]; 4]]) {
+        core::multiply_4x4_batch(parent, children)
+    }
+
+    fn to_f32_4x4(m: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
+        m
+    }
+}
+
+impl Scalar for f64 {
+    fn parse_translation(s: &str) -> Result<[f64; 3], ParseError> {
+        core::parse_doubles(s)
+    }
+
+    fn from_f32(v: f32) -> f64 {
+        f64::from(v)
+    }
+
+    fn from_f64(v: f64) -> f64 {
+        v
+    }
+
+    fn multiply_batch(parent: [[f64; 4]; 4], children: &mut [[[f64; 4]; 4]]) {
+        for child in children.iter_mut() {
+            *child = core::multiply_4x4_f64(parent, *child);
+        }
+    }
+
+    fn to_f32_4x4(m: [[f64; 4]; 4]) -> [[f32; 4]; 4] {
+        core::to_f32_4x4(m)
+    }
+}
+
+// Depth-first traversal with an explicit stack so deep assemblies can't overflow the call
+// stack. Children are pushed in reverse so items come out in document order, and all of an
+// assembly's instances are placed in one batch when it is entered.
+struct Traversal<T> {
+    active: Vec<bool>,
+    assemblies: usize,
+    bounds: Vec<Option<BoundingBox3>>,
+    hidden: usize,
+    measuring: bool,
+    open: Vec<Open>,
+    stack: Vec<Step>,
+    transforms: Vec<[[T; 4]; 4]>,
+}
+
+impl<T: Scalar> Traversal<T> {
+    // Without precomputed assembly bounds this is a measuring pass that only records them. A
+    // forest of several roots tells them apart by prefixing ids with the root component index.
+    fn new(
+        component_count: usize,
+        roots: &[usize],
+        bounds: Option<Vec<Option<BoundingBox3>>>,
+    ) -> Traversal<T> {
+        let stack = roots
+            .iter()
+            .rev()
+            .map(|&component_idx| Step::Root {
+                component_idx,
+                supplied_id: match roots.len() {
+                    1 => PATH_ID_SEPARATOR.to_string(),
+                    _ => child_id(PATH_ID_SEPARATOR, &component_idx.to_string()),
+                },
+            })
+            .collect();
+
+        Traversal {
+            active: vec![false; component_count],
+            assemblies: 0,
+            measuring: bounds.is_none(),
+            bounds: bounds.unwrap_or_default(),
+            hidden: 0,
+            open: vec![],
+            stack,
+            transforms: vec![],
+        }
+    }
+
+    // Runs the measuring pass and returns a traversal that streams items with its bounds.
+    fn measured(scene: &Scene, roots: &[usize]) -> Result<Traversal<T>, PvsError> {
+        let mut measure = Traversal::<T>::new(scene.components.len(), roots, None);
+        while let Some(result) = measure.next(scene) {
+            result?;
+        }
+
+        Ok(Traversal::new(
+            scene.components.len(),
+            roots,
+            Some(measure.bounds),
+        ))
+    }
+
+    fn next(&mut self, scene: &Scene) -> Option<Result<SceneItem, PvsError>> {
+        while let Some(step) = self.stack.pop() {
+            let result = match step {
+                Step::Root {
+                    component_idx,
+                    supplied_id,
+                } => self.enter(
+                    scene,
+                    component_idx,
+                    None,
+                    supplied_id,
+                    None,
+                    Visibility::default(),
+                ),
+                Step::Instance {
+                    component_idx,
+                    instance_idx,
+                    placed,
+                } => self.enter_instance(scene, component_idx, instance_idx, placed),
+                Step::Exit => {
+                    self.exit();
+                    Ok(None)
+                }
+            };
+            match result {
+                Ok(Some(item)) => return Some(Ok(item)),
+                Ok(None) => {}
+                Err(e) => {
+                    self.stack.clear();
+                    self.transforms.clear();
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        None
+    }
+
+    fn enter_instance(
+        &mut self,
+        scene: &Scene,
+        component_idx: usize,
+        instance_idx: usize,
+        placed: bool,
+    ) -> Result<Option<SceneItem>, PvsError> {
+        let component = &scene.components[component_idx];
+        let instance = &component.component_instances[instance_idx];
+        let transform = if placed { self.transforms.pop() } else { None };
+        let idx = instance_index(&scene.components, component, instance)?;
+        let parent = self.open.last().expect("Instance without open parent");
+        let supplied_id = child_id(&parent.supplied_id, &instance.id);
+        if self.active[idx] {
+            return Err(PvsError::Cycle {
+                path: supplied_id,
+                index: idx,
+            });
+        }
+        if let Some(max_depth) = scene.options.max_depth {
+            if parent.depth >= max_depth {
+                return Err(PvsError::MaxDepthExceeded {
+                    path: supplied_id,
+                    max_depth,
+                });
+            }
+        }
+
+        let visibility = Visibility {
+            children_hidden: parent.visibility.children_hidden
+                || instance.hide_child.unwrap_or(false),
+            hidden: parent.visibility.children_hidden || instance.hide_self.unwrap_or(false),
+        };
+        if !self.measuring {
+            if let Some(t) = transform {
+                debug!("Resolved transform for {}: {:?}", supplied_id, t);
+            }
+            if visibility.hidden {
+                debug!("Hiding instance {}", supplied_id);
+            }
+        }
+        if visibility.hidden {
+            self.hidden += 1;
+        }
+        self.enter(
+            scene,
+            idx,
+            Some((component, instance)),
+            supplied_id,
+            transform,
+            visibility,
+        )
+    }
+
+    // `parent` is the assembly and instance placing the component, which is None for roots.
+    fn enter(
+        &mut self,
+        scene: &Scene,
+        component_idx: usize,
+        parent: Option<(&Component, &ComponentInstance)>,
+        supplied_id: String,
+        transform: Option<[[T; 4]; 4]>,
+        visibility: Visibility,
+    ) -> Result<Option<SceneItem>, PvsError> {
+        let component = &scene.components[component_idx];
+        let world = transform.map(T::to_f32_4x4);
+        let (parent_id, depth, item_depth) = match self.open.last() {
+            Some(p) => (Some(p.item_id.clone()), p.depth + 1, p.item_depth + 1),
+            None => (None, 0, 0),
+        };
+        let emitted = !(visibility.hidden && scene.options.hidden == HiddenInstances::Prune);
+
+        if !component.component_instances.is_empty() {
+            let ordinal = self.assemblies;
+            self.assemblies += 1;
+            if self.measuring {
+                self.bounds.push(None);
+            }
+            self.active[component_idx] = true;
+            self.stack.push(Step::Exit);
+            let placed_from = self.transforms.len();
+            for (instance_idx, instance) in component.component_instances.iter().enumerate().rev() {
+                if scene.options.hidden == HiddenInstances::Skip
+                    && (instance.hide_self.unwrap_or(false) || instance.hide_child.unwrap_or(false))
+                {
+                    if !self.measuring {
+                        debug!(
+                            "Skipping hidden instance {}",
+                            child_id(&supplied_id, &instance.id)
+                        );
+                    }
+                    self.hidden += 1;
+                    continue;
+                };
+                let local = instance_transform(scene, component, instance, self.measuring)?;
+                // Leaves without a bounding box don't need a world transform to be measured.
+                let placed = !self.measuring
+                    || scene.has_bounds(instance_index(&scene.components, component, instance)?);
+                if placed {
+                    self.transforms.push(local);
+                }
+                self.stack.push(Step::Instance {
+                    component_idx,
+                    instance_idx,
+                    placed,
+                });
+            }
+            if let Some(t) = transform {
+                T::multiply_batch(t, &mut self.transforms[placed_from..]);
+            }
+            let (item_id, item_depth) = match (emitted, self.open.last()) {
+                (false, Some(p)) => (p.item_id.clone(), p.item_depth),
+                _ => (supplied_id.clone(), item_depth),
             };
+            self.open.push(Open {
+                bounds: None,
+                component_idx,
+                depth,
+                item_depth,
+                item_id,
+                ordinal,
+                supplied_id: supplied_id.clone(),
+                visibility,
+            });
+            if self.measuring && emitted {
+                material::appearance(scene, component_idx, parent)?;
+            }
+            if self.measuring || !emitted {
+                return Ok(None);
+            }
 
-            let inst_transform = to_4x4(
-                core::to_arr_9(core::to_float_arr(&match &comp_inst.orientation {
-                    Some(o) => o,
-                    None => DEFAULT_ORIENTATION,
-                })?),
-                core::to_arr_3(core::to_float_arr(&match &comp_inst.translation {
-                    Some(t) => t,
-                    None => DEFAULT_TRANSLATION,
-                })?),
-                1000.0,
+            let mut item = SceneItem::new(
+                supplied_id,
+                parent_id,
+                item_depth,
+                component.name.clone(),
+                scene.metadata(component_idx),
             );
-            let idx: usize = comp_inst.index.parse()?;
-            add_items(
-                components,
-                &components[idx],
-                &format!("{}/{}", path_id, comp_inst.id),
-                Some(match transform {
-                    Some(t) => core::multiply_4x4(t, inst_transform),
-                    None => inst_transform,
-                }),
-                items,
-            )?;
-        }
-        return Ok(());
-    } else {
-        match &component.shape_source {
-            Some(ss) => {
-                items.push(SceneItem::new(
-                    path_id,
-                    &component.name,
-                    DEFAULT_SUPPLIED_REVISION_ID,
-                    Some(&ss.file_name),
-                    transform,
-                ));
-                return Ok(());
+            item.bounding_box = self.bounds[ordinal];
+            item.material_override = material::material(scene, component_idx, parent)?;
+            item.mirrored = world.is_some_and(is_mirrored);
+            item.visible = !visibility.hidden;
+            Ok(Some(item))
+        } else if let Some(ss) = &component.shape_source {
+            let bounds = match &ss.bbox {
+                Some(b) => {
+                    let instance_id = supplied_id.rsplit(PATH_ID_SEPARATOR).next();
+                    let local = to_bbox(
+                        parse_floats(
+                            b,
+                            "bbox",
+                            core::parse_floats::<6>,
+                            component,
+                            instance_id.unwrap_or(""),
+                        )?,
+                        scene.scale as f32,
+                    );
+                    Some(match world {
+                        Some(t) => local.transform(t),
+                        None => local,
+                    })
+                }
+                None => None,
+            };
+            if !emitted {
+                return Ok(None);
+            }
+            if let Some(parent) = self.open.last_mut() {
+                parent.bounds = union(parent.bounds, bounds);
+            }
+            if self.measuring {
+                material::appearance(scene, component_idx, parent)?;
+                return Ok(None);
             }
-            None => return Ok(()),
+
+            let mut item = SceneItem::new(
+                supplied_id,
+                parent_id,
+                item_depth,
+                component.name.clone(),
+                scene.metadata(component_idx),
+            );
+            item.bounding_box = bounds;
+            item.material_override = material::material(scene, component_idx, parent)?;
+            item.mirrored = world.is_some_and(is_mirrored);
+            item.source = Some(Source {
+                file_name: ss.file_name.clone(),
+                supplied_part_id: component.name.clone(),
+                supplied_revision_id: scene.revision(component_idx).to_string(),
+            });
+            item.transform = world
+                .map(|t| match scene.options.snap_epsilon {
+                    Some(epsilon) => core::snap_4x4(t, epsilon),
+                    None => t,
+                })
+                .filter(|t| !core::is_4x4_identity(*t))
+                .map(core::to_transform);
+            item.visible = !visibility.hidden;
+            Ok(Some(item))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn exit(&mut self) {
+        let open = self.open.pop().expect("Exit without open assembly");
+        self.active[open.component_idx] = false;
+        if self.measuring {
+            self.bounds[open.ordinal] = open.bounds;
+        }
+        if let Some(parent) = self.open.last_mut() {
+            parent.bounds = union(parent.bounds, open.bounds);
         }
     }
 }
 
-fn to_4x4(orientation: [f32; 9], translation: [f32; 3], scale: f32) -> [[f32; 4]; 4] {
-    return [
+impl Scene {
+    fn roots(&self) -> Result<Vec<usize>, PvsError> {
+        if self.components.is_empty() {
+            return Err(PvsError::EmptyStructure);
+        }
+        match self.options.root {
+            RootSelection::All => match self.unreferenced()? {
+                roots if roots.is_empty() => Err(PvsError::NoRoot),
+                roots => Ok(roots),
+            },
+            _ => Ok(vec![self.root()?]),
+        }
+    }
+
+    // The one root of every selection but All, which only roots() handles.
+    fn root(&self) -> Result<usize, PvsError> {
+        let not_found = || PvsError::RootNotFound {
+            root: self.options.root.to_string(),
+        };
+
+        match &self.options.root {
+            RootSelection::Detect => self.single_root(self.unreferenced()?),
+            RootSelection::Last => Ok(self.components.len() - 1),
+            RootSelection::Index(idx) if *idx < self.components.len() => Ok(*idx),
+            RootSelection::Index(_) => Err(not_found()),
+            RootSelection::All => unreachable!("All selects several roots"),
+            RootSelection::Name(name) => {
+                let named: Vec<usize> = (0..self.components.len())
+                    .filter(|&idx| &self.components[idx].name == name)
+                    .collect();
+                match named.len() {
+                    0 => Err(not_found()),
+                    1 => Ok(named[0]),
+                    _ => {
+                        let unreferenced = self.unreferenced()?;
+                        self.single_root(
+                            named
+                                .into_iter()
+                                .filter(|idx| unreferenced.contains(idx))
+                                .collect(),
+                        )
+                    }
+                }
+            }
+        }
+    }
+
+    fn single_root(&self, candidates: Vec<usize>) -> Result<usize, PvsError> {
+        match candidates.len() {
+            0 => Err(PvsError::NoRoot),
+            1 => Ok(candidates[0]),
+            _ => Err(PvsError::AmbiguousRoot {
+                candidates: candidates
+                    .into_iter()
+                    .map(|idx| (idx, self.components[idx].name.clone()))
+                    .collect(),
+            }),
+        }
+    }
+
+    // Components no instance refers to, in document order.
+    fn unreferenced(&self) -> Result<Vec<usize>, PvsError> {
+        let mut referenced = vec![false; self.components.len()];
+        for component in &self.components {
+            for instance in &component.component_instances {
+                referenced[instance_index(&self.components, component, instance)?] = true;
+            }
+        }
+
+        Ok((0..self.components.len())
+            .filter(|&idx| !referenced[idx])
+            .collect())
+    }
+
+    fn has_bounds(&self, component_idx: usize) -> bool {
+        let component = &self.components[component_idx];
+        !component.component_instances.is_empty()
+            || component
+                .shape_source
+                .as_ref()
+                .is_some_and(|ss| ss.bbox.is_some())
+    }
+
+    fn metadata(&self, component_idx: usize) -> BTreeMap<String, String> {
+        self.properties[component_idx]
+            .as_ref()
+            .map(|p| p.metadata(&self.options.properties))
+            .unwrap_or_default()
+    }
+
+    fn revision(&self, component_idx: usize) -> &str {
+        self.property(component_idx, REVISION_PROPERTY)
+            .unwrap_or(DEFAULT_SUPPLIED_REVISION_ID)
+    }
+
+    fn property(&self, component_idx: usize, name: &str) -> Option<&str> {
+        self.properties[component_idx]
+            .as_ref()
+            .and_then(|p| p.value(name))
+    }
+}
+
+fn child_id(parent_id: &str, instance_id: &str) -> String {
+    let mut id = String::with_capacity(parent_id.len() + 1 + instance_id.len());
+    id.push_str(parent_id);
+    if parent_id != PATH_ID_SEPARATOR {
+        id.push_str(PATH_ID_SEPARATOR);
+    }
+    id.push_str(instance_id);
+    id
+}
+
+fn instance_index(
+    components: &[Component],
+    component: &Component,
+    comp_inst: &ComponentInstance,
+) -> Result<usize, PvsError> {
+    match comp_inst.index.parse() {
+        Ok(idx) if idx < components.len() => Ok(idx),
+        _ => Err(PvsError::InvalidIndex {
+            component: component.name.clone(),
+            instance_id: comp_inst.id.clone(),
+            index: comp_inst.index.clone(),
+        }),
+    }
+}
+
+fn instance_transform<T: Scalar>(
+    scene: &Scene,
+    component: &Component,
+    comp_inst: &ComponentInstance,
+    measuring: bool,
+) -> Result<[[T; 4]; 4], PvsError> {
+    let orientation = parse_floats(
+        comp_inst
+            .orientation
+            .as_deref()
+            .unwrap_or(DEFAULT_ORIENTATION),
+        "orientation",
+        core::parse_floats::<9>,
+        component,
+        &comp_inst.id,
+    )?;
+    let orientation = check_orientation(scene, component, comp_inst, orientation, measuring)?;
+    let translation = comp_inst
+        .translation
+        .as_deref()
+        .unwrap_or(DEFAULT_TRANSLATION);
+
+    Ok(to_4x4(
+        orientation.map(T::from_f32),
+        parse_floats(
+            translation,
+            "translation",
+            T::parse_translation,
+            component,
+            &comp_inst.id,
+        )?,
+        T::from_f64(scene.scale),
+    ))
+}
+
+// Orientations list the matrix column by column. Warnings are left to the streaming pass, so
+// measuring only checks when the result can change or fail.
+fn check_orientation(
+    scene: &Scene,
+    component: &Component,
+    comp_inst: &ComponentInstance,
+    orientation: [f32; 9],
+    measuring: bool,
+) -> Result<[f32; 9], PvsError> {
+    match scene.options.orientations {
+        OrientationCheck::Ignore => return Ok(orientation),
+        OrientationCheck::Warn if measuring => return Ok(orientation),
+        _ => {}
+    }
+    let m = [
+        [orientation[0], orientation[3], orientation[6]],
+        [orientation[1], orientation[4], orientation[7]],
+        [orientation[2], orientation[5], orientation[8]],
+    ];
+    let error = core::orthonormality_error(&m);
+    if error <= ORIENTATION_TOLERANCE {
+        return Ok(orientation);
+    }
+
+    let invalid = PvsError::InvalidOrientation {
+        component: component.name.clone(),
+        instance_id: comp_inst.id.clone(),
+        determinant: core::determinant_3x3(&m),
+        error,
+    };
+    match scene.options.orientations {
+        OrientationCheck::Ignore => Ok(orientation),
+        OrientationCheck::Warn => {
+            warn!("{}", invalid);
+            Ok(orientation)
+        }
+        OrientationCheck::Error => Err(invalid),
+        OrientationCheck::Orthonormalize => match core::orthonormalize(&m) {
+            Some(r) => {
+                if !measuring {
+                    warn!("{}, orthonormalizing it", invalid);
+                }
+                Ok([
+                    r[0][0], r[1][0], r[2][0], r[0][1], r[1][1], r[2][1], r[0][2], r[1][2], r[2][2],
+                ])
+            }
+            None => Err(invalid),
+        },
+    }
+}
+
+fn is_mirrored(t: [[f32; 4]; 4]) -> bool {
+    core::determinant_3x3(&[
+        [t[0][0], t[0][1], t[0][2]],
+        [t[1][0], t[1][1], t[1][2]],
+        [t[2][0], t[2][1], t[2][2]],
+    ]) < 0.0
+}
+
+fn parse_floats<A>(
+    value: &str,
+    attribute: &'static str,
+    parse: fn(&str) -> Result<A, ParseError>,
+    component: &Component,
+    instance_id: &str,
+) -> Result<A, PvsError> {
+    parse(value).map_err(|e| match e {
+        ParseError::InvalidNumber {
+            index,
+            offset,
+            token,
+        } => PvsError::InvalidNumber {
+            component: component.name.clone(),
+            instance_id: instance_id.to_string(),
+            attribute,
+            value: value.to_string(),
+            index,
+            offset,
+            token: token.into_boxed_str(),
+        },
+        ParseError::WrongCount { expected, actual } => PvsError::InvalidArity {
+            component: component.name.clone(),
+            instance_id: instance_id.to_string(),
+            attribute,
+            expected,
+            actual,
+        },
+    })
+}
+
+fn union(a: Option<BoundingBox3>, b: Option<BoundingBox3>) -> Option<BoundingBox3> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.union(&b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn to_bbox(bbox: [f32; 6], scale: f32) -> BoundingBox3 {
+    let mut scaled = bbox;
+    for v in scaled.iter_mut() {
+        *v *= scale;
+    }
+    BoundingBox3::from_arr_6(scaled)
+}
+
+fn to_4x4<T>(orientation: [T; 9], translation: [T; 3], scale: T) -> [[T; 4]; 4]
+where
+    T: Copy + Default + From<u8> + Mul<Output = T>,
+{
+    [
         [
             orientation[0],
             orientation[3],
@@ -246,14 +1224,796 @@ fn to_4x4(orientation: [f32; 9], translation: [f32; 3], scale: f32) -> [[f32; 4]
             orientation[8],
             translation[2] * scale,
         ],
-        [0.0, 0.0, 0.0, 1.0],
-    ];
+        [T::default(), T::default(), T::default(), T::from(1)],
+    ]
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    fn create_items(xml: &str, options: &ConvertOptions) -> Result<Vec<SceneItem>, PvsError> {
+        items_from_reader(xml.as_bytes(), options)?.collect()
+    }
+
+    fn revisions(items: &[SceneItem]) -> Vec<&str> {
+        items
+            .iter()
+            .filter_map(|i| i.source.as_ref())
+            .map(|s| s.supplied_revision_id.as_str())
+            .collect()
+    }
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn revision_defaults_when_absent() {
+        let xml = r#"<PV_FILE><section_structure>
+                <component name="A"><shape_source file_name="a.ol"/></component>
+                <component name="B"><shape_source file_name="b.ol"/></component>
+                <component name="Root">
+                    <component_instance index="0" id="1"/>
+                    <component_instance index="1" id="2"/>
+                </component>
+            </section_structure><section_properties>
+                <property_component_ref><property name="revId" value="C"/></property_component_ref>
+                <property_component_ref><property name="owner" value="me"/></property_component_ref>
+            </section_properties></PV_FILE>"#;
+
+        assert_eq!(
+            vec!["C", "1"],
+            revisions(&create_items(xml, &ConvertOptions::default()).unwrap())
+        );
+    }
+
+    #[test]
+    fn revision_uses_explicit_index() {
+        let xml = r#"<PV_FILE><section_structure>
+                <component name="A"><shape_source file_name="a.ol"/></component>
+                <component name="B"><shape_source file_name="b.ol"/></component>
+                <component name="Root">
+                    <component_instance index="0" id="1"/>
+                    <component_instance index="1" id="2"/>
+                </component>
+            </section_structure><section_properties>
+                <property_component_ref index="1"><property name="revId" value="D"/></property_component_ref>
+            </section_properties></PV_FILE>"#;
+
+        assert_eq!(
+            vec!["1", "D"],
+            revisions(&create_items(xml, &ConvertOptions::default()).unwrap())
+        );
+    }
+
+    #[test]
+    fn property_index_out_of_range_is_error() {
+        assert_eq!(
+            "Property component ref has invalid index '99'",
+            error(
+                r#"<PV_FILE><section_structure>
+                    <component name="A"><shape_source file_name="a.ol"/></component>
+                    <component name="Root"><component_instance index="0" id="1"/></component>
+                </section_structure><section_properties>
+                    <property_component_ref index="99"><property name="revId" value="D"/></property_component_ref>
+                </section_properties></PV_FILE>"#
+            )
+        );
+    }
+
+    #[test]
+    fn metadata_filters_properties() {
+        let xml = r#"<PV_FILE><section_structure>
+                <component name="A"><shape_source file_name="a.ol"/></component>
+                <component name="Root"><component_instance index="0" id="1"/></component>
+            </section_structure><section_properties>
+                <property_component_ref>
+                    <property name="revId" value="C"/>
+                    <property name="material" value="Steel"/>
+                    <property name="owner" value="me"/>
+                </property_component_ref>
+            </section_properties></PV_FILE>"#;
+        let names = |filter: PropertyFilter| {
+            let options = ConvertOptions {
+                properties: filter,
+                ..ConvertOptions::default()
+            };
+            let items = create_items(xml, &options).unwrap();
+            items[1].metadata.keys().cloned().collect::<Vec<String>>()
+        };
+        let set = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+
+        assert_eq!(
+            vec!["material", "owner", "revId"],
+            names(PropertyFilter::All)
+        );
+        assert_eq!(
+            vec!["material"],
+            names(PropertyFilter::Allow(set(&["material", "color"])))
+        );
+        assert_eq!(
+            vec!["material", "revId"],
+            names(PropertyFilter::Deny(set(&["owner"])))
+        );
+    }
+
+    fn error(xml: &str) -> String {
+        create_items(xml, &ConvertOptions::default())
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn empty_structure_is_error() {
+        assert_eq!(
+            "PVS file has no components",
+            error("<PV_FILE><section_structure></section_structure></PV_FILE>")
+        );
+    }
+
+    #[test]
+    fn invalid_index_is_error() {
+        assert_eq!(
+            "Instance '7' in component 'Root' has invalid index '3'",
+            error(
+                r#"<PV_FILE><section_structure>
+                    <component name="A"><shape_source file_name="a.ol"/></component>
+                    <component name="Root"><component_instance index="3" id="7"/></component>
+                </section_structure></PV_FILE>"#
+            )
+        );
+    }
+
+    #[test]
+    fn wrong_orientation_arity_is_error() {
+        assert_eq!(
+            "Instance '7' in component 'Root' has 3 orientation values but expected 9",
+            error(
+                r#"<PV_FILE><section_structure>
+                    <component name="A"><shape_source file_name="a.ol"/></component>
+                    <component name="Root">
+                        <component_instance index="0" id="7" orientation="1,0,0"/>
+                    </component>
+                </section_structure></PV_FILE>"#
+            )
+        );
+    }
+
+    #[test]
+    fn wrong_translation_arity_is_error() {
+        assert_eq!(
+            "Instance '7' in component 'Root' has 2 translation values but expected 3",
+            error(
+                r#"<PV_FILE><section_structure>
+                    <component name="A"><shape_source file_name="a.ol"/></component>
+                    <component name="Root">
+                        <component_instance index="0" id="7" translation="1,0"/>
+                    </component>
+                </section_structure></PV_FILE>"#
+            )
+        );
+    }
+
+    #[test]
+    fn invalid_number_is_error() {
+        assert_eq!(
+            "Instance '7' in component 'A' has invalid bbox '0,0,0,1,x,1' (value 4 'x' at offset 8)",
+            error(
+                r#"<PV_FILE><section_structure>
+                    <component name="A"><shape_source file_name="a.ol" bbox="0,0,0,1,x,1"/></component>
+                    <component name="Root"><component_instance index="0" id="7"/></component>
+                </section_structure></PV_FILE>"#
+            )
+        );
+    }
+
+    #[test]
+    fn self_reference_is_error() {
+        assert_eq!(
+            "Cyclic component reference /1/2 -> index 0",
+            error(
+                r#"<PV_FILE><section_structure>
+                    <component name="A">
+                        <component_instance index="0" id="2"/>
+                    </component>
+                    <component name="Root"><component_instance index="0" id="1"/></component>
+                </section_structure></PV_FILE>"#
+            )
+        );
+    }
+
+    #[test]
+    fn two_node_cycle_is_error() {
+        assert_eq!(
+            "Cyclic component reference /109/104/105 -> index 0",
+            error(
+                r#"<PV_FILE><section_structure>
+                    <component name="A"><component_instance index="1" id="104"/></component>
+                    <component name="B"><component_instance index="0" id="105"/></component>
+                    <component name="Root"><component_instance index="0" id="109"/></component>
+                </section_structure></PV_FILE>"#
+            )
+        );
+    }
+
+    #[test]
+    fn shared_component_is_not_cycle() {
+        let xml = r#"<PV_FILE><section_structure>
+                <component name="A"><shape_source file_name="a.ol"/></component>
+                <component name="B"><component_instance index="0" id="1"/></component>
+                <component name="Root">
+                    <component_instance index="1" id="2"/>
+                    <component_instance index="1" id="3"/>
+                </component>
+            </section_structure></PV_FILE>"#;
+
+        assert_eq!(
+            5,
+            create_items(xml, &ConvertOptions::default()).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn max_depth_exceeded_is_error() {
+        let xml = r#"<PV_FILE><section_structure>
+                <component name="A"><shape_source file_name="a.ol"/></component>
+                <component name="B"><component_instance index="0" id="2"/></component>
+                <component name="Root"><component_instance index="1" id="1"/></component>
+            </section_structure></PV_FILE>"#;
+        let deep = ConvertOptions {
+            max_depth: Some(2),
+            ..ConvertOptions::default()
+        };
+        let shallow = ConvertOptions {
+            max_depth: Some(1),
+            ..ConvertOptions::default()
+        };
+
+        assert_eq!(3, create_items(xml, &deep).unwrap().len());
+        assert_eq!(
+            "Instance /1/2 exceeds maximum depth of 1",
+            create_items(xml, &shallow).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn deep_hierarchy_does_not_overflow() {
+        let depth = 5_000;
+        let mut xml = String::from(
+            r#"<PV_FILE><section_structure>
+                <component name="Leaf"><shape_source file_name="leaf.ol"/></component>"#,
+        );
+        for i in 1..=depth {
+            xml.push_str(&format!(
+                r#"<component name="C{}"><component_instance index="{}" id="{}"/></component>"#,
+                i,
+                i - 1,
+                i
+            ));
+        }
+        xml.push_str("</section_structure></PV_FILE>");
+
+        let items = create_items(&xml, &ConvertOptions::default()).unwrap();
+
+        assert_eq!(depth + 1, items.len());
+        assert_eq!(depth, items[depth].depth);
+        assert!(items[depth].source.is_some());
+    }
+
+    #[test]
+    fn traversal_error_is_returned_before_streaming() {
+        let xml = r#"<PV_FILE><section_structure>
+            <component name="A"><shape_source file_name="a.ol"/></component>
+            <component name="Root">
+                <component_instance index="0" id="1"/>
+                <component_instance index="0" id="2" translation="1,2"/>
+            </component>
+        </section_structure></PV_FILE>"#;
+
+        assert!(matches!(
+            items_from_reader(xml.as_bytes(), &ConvertOptions::default()),
+            Err(PvsError::InvalidArity { .. })
+        ));
+    }
+
+    #[test]
+    fn material_error_is_returned_before_streaming() {
+        let xml = r#"<PV_FILE><section_structure>
+            <component name="A"><shape_source file_name="a.ol"/></component>
+            <component name="Root">
+                <component_instance index="0" id="1"/>
+                <component_instance index="0" id="2" transparency="x"/>
+            </component>
+        </section_structure></PV_FILE>"#;
+
+        assert!(matches!(
+            items_from_reader(xml.as_bytes(), &ConvertOptions::default()),
+            Err(PvsError::InvalidNumber { .. })
+        ));
+    }
+
+    #[test]
+    fn scales_every_unit_pair() {
+        use LengthUnit::*;
+        let expected = [
+            (Meter, [1.0, 100.0, 1000.0, 1000.0 / 25.4, 1000.0 / 304.8]),
+            (Centimeter, [0.01, 1.0, 10.0, 10.0 / 25.4, 10.0 / 304.8]),
+            (Millimeter, [0.001, 0.1, 1.0, 1.0 / 25.4, 1.0 / 304.8]),
+            (Inch, [0.0254, 2.54, 25.4, 1.0, 1.0 / 12.0]),
+            (Foot, [0.3048, 30.48, 304.8, 12.0, 1.0]),
+        ];
+
+        for (source, factors) in expected.iter() {
+            for (target, factor) in LengthUnit::ALL.iter().zip(factors.iter()) {
+                let actual = source.scale_to(*target);
+                assert!(
+                    (actual - factor).abs() <= factor * 1e-6,
+                    "{} -> {}: {} != {}",
+                    source,
+                    target,
+                    actual,
+                    factor
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn parses_length_units() {
+        for unit in LengthUnit::ALL.iter() {
+            assert_eq!(*unit, unit.to_string().parse::<LengthUnit>().unwrap());
+        }
+        assert!("yd".parse::<LengthUnit>().is_err());
+    }
+
+    #[test]
+    fn converts_translation_to_target_units() {
+        let xml = r#"<PV_FILE><section_structure>
+            <component name="A"><shape_source file_name="a.ol"/></component>
+            <component name="Root">
+                <component_instance index="0" id="1" translation="1,2,3"/>
+            </component>
+        </section_structure></PV_FILE>"#;
+        let options = ConvertOptions {
+            source_units: LengthUnit::Foot,
+            target_units: LengthUnit::Inch,
+            ..ConvertOptions::default()
+        };
+
+        let items = create_items(xml, &options).unwrap();
+        let transform = items[1].transform.as_ref().unwrap();
+
+        assert_eq!(
+            [12.0, 24.0, 36.0],
+            [transform.r0.w, transform.r1.w, transform.r2.w]
+        );
+    }
+
+    const HIDDEN_XML: &str = r#"<PV_FILE><section_structure>
+        <component name="Leaf"><shape_source file_name="leaf.ol" bbox="0,0,0,1,1,1"/></component>
+        <component name="A"><component_instance index="0" id="3" translation="1,0,0"/></component>
+        <component name="B"><component_instance index="0" id="4" translation="2,0,0"/></component>
+        <component name="Root">
+            <component_instance index="1" id="1" hide_self="true"/>
+            <component_instance index="2" id="2" hide_child="true"/>
+        </component>
+    </section_structure></PV_FILE>"#;
+
+    fn summary(items: &[SceneItem]) -> Vec<(&str, Option<&str>, usize, bool)> {
+        items
+            .iter()
+            .map(|i| {
+                (
+                    i.supplied_id.as_str(),
+                    i.parent_id.as_deref(),
+                    i.depth,
+                    i.visible,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn skips_hidden_subtrees_by_default() {
+        let items = create_items(HIDDEN_XML, &ConvertOptions::default()).unwrap();
+
+        assert_eq!(vec![("/", None, 0, true)], summary(&items));
+        assert_eq!(None, items[0].bounding_box);
+    }
+
+    #[test]
+    fn keeps_hidden_instances_as_invisible() {
+        let options = ConvertOptions {
+            hidden: HiddenInstances::Keep,
+            ..ConvertOptions::default()
+        };
+        let items = create_items(HIDDEN_XML, &options).unwrap();
+
+        assert_eq!(
+            vec![
+                ("/", None, 0, true),
+                ("/1", Some("/"), 1, false),
+                ("/1/3", Some("/1"), 2, true),
+                ("/2", Some("/"), 1, true),
+                ("/2/4", Some("/2"), 2, false),
+            ],
+            summary(&items)
+        );
+    }
+
+    #[test]
+    fn prunes_hidden_nodes_and_subtrees() {
+        let options = ConvertOptions {
+            hidden: HiddenInstances::Prune,
+            ..ConvertOptions::default()
+        };
+        let mut items = items_from_reader(HIDDEN_XML.as_bytes(), &options).unwrap();
+        let emitted: Vec<SceneItem> = items.by_ref().map(Result::unwrap).collect();
+
+        assert_eq!(
+            vec![
+                ("/", None, 0, true),
+                ("/1/3", Some("/"), 1, true),
+                ("/2", Some("/"), 1, true)
+            ],
+            summary(&emitted)
+        );
+        assert_eq!(2, items.hidden_instances());
+        assert_eq!(
+            Some(BoundingBox3::from_arr_6([
+                1000.0, 0.0, 0.0, 2000.0, 1000.0, 1000.0
+            ])),
+            emitted[0].bounding_box
+        );
+        assert_eq!(None, emitted[2].bounding_box);
+    }
+
+    const TWO_ROOTS_XML: &str = r#"<PV_FILE><section_structure>
+        <component name="Front"><component_instance index="2" id="1"/></component>
+        <component name="Back"><component_instance index="2" id="2"/></component>
+        <component name="Leaf"><shape_source file_name="leaf.ol"/></component>
+    </section_structure></PV_FILE>"#;
+
+    fn root_name(xml: &str, root: RootSelection) -> Result<String, PvsError> {
+        let options = ConvertOptions {
+            root,
+            ..ConvertOptions::default()
+        };
+        Ok(create_items(xml, &options)?.remove(0).name)
+    }
+
+    #[test]
+    fn detects_root_placed_first() {
+        let xml = r#"<PV_FILE><section_structure>
+            <component name="Root"><component_instance index="1" id="1"/></component>
+            <component name="A"><shape_source file_name="a.ol"/></component>
+        </section_structure></PV_FILE>"#;
+
+        assert_eq!("Root", root_name(xml, RootSelection::Detect).unwrap());
+        assert_eq!("A", root_name(xml, RootSelection::Last).unwrap());
+    }
+
+    #[test]
+    fn multiple_roots_need_a_strategy() {
+        assert_eq!(
+            "PVS file has several candidate roots (0 'Front', 1 'Back'), choose one by index or name",
+            root_name(TWO_ROOTS_XML, RootSelection::Detect)
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "Back",
+            root_name(TWO_ROOTS_XML, RootSelection::Index(1)).unwrap()
+        );
+        assert_eq!(
+            "Front",
+            root_name(TWO_ROOTS_XML, RootSelection::Name("Front".to_string())).unwrap()
+        );
+    }
+
+    #[test]
+    fn missing_root_is_error() {
+        assert_eq!(
+            "Root component index:3 not found",
+            root_name(TWO_ROOTS_XML, RootSelection::Index(3))
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "Root component name:Side not found",
+            root_name(TWO_ROOTS_XML, RootSelection::Name("Side".to_string()))
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn fully_referenced_structure_has_no_root() {
+        let xml = r#"<PV_FILE><section_structure>
+            <component name="A"><component_instance index="1" id="1"/></component>
+            <component name="B"><component_instance index="0" id="2"/></component>
+        </section_structure></PV_FILE>"#;
+
+        assert!(matches!(
+            root_name(xml, RootSelection::Detect),
+            Err(PvsError::NoRoot)
+        ));
+    }
+
+    #[test]
+    fn parses_root_selection() {
+        for root in [
+            RootSelection::Detect,
+            RootSelection::Last,
+            RootSelection::Index(4),
+            RootSelection::Name("TN1, Model".to_string()),
+            RootSelection::All,
+        ] {
+            assert_eq!(root, root.to_string().parse::<RootSelection>().unwrap());
+        }
+        assert!("index:x".parse::<RootSelection>().is_err());
+        assert!("first".parse::<RootSelection>().is_err());
+    }
+
+    #[test]
+    fn forest_has_one_root_item_per_unreferenced_component() {
+        let options = ConvertOptions {
+            root: RootSelection::All,
+            ..ConvertOptions::default()
+        };
+        let items = create_items(TWO_ROOTS_XML, &options).unwrap();
+
+        assert_eq!(
+            vec![
+                ("/0", None, 0, true),
+                ("/0/1", Some("/0"), 1, true),
+                ("/1", None, 0, true),
+                ("/1/2", Some("/1"), 1, true),
+            ],
+            summary(&items)
+        );
+    }
+
+    #[test]
+    fn forest_of_one_root_matches_detection() {
+        let xml = r#"<PV_FILE><section_structure>
+            <component name="A"><shape_source file_name="a.ol"/></component>
+            <component name="Root"><component_instance index="0" id="1"/></component>
+        </section_structure></PV_FILE>"#;
+        let options = ConvertOptions {
+            root: RootSelection::All,
+            ..ConvertOptions::default()
+        };
+
+        assert_eq!(
+            create_items(xml, &ConvertOptions::default()).unwrap(),
+            create_items(xml, &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn parses_material_overrides() {
+        let xml = r##"<PV_FILE><section_structure>
+            <component name="A"><shape_source file_name="a.ol"/></component>
+            <component name="Root">
+                <component_instance index="0" id="1"/>
+                <component_instance index="0" id="2" diffuse_color="1,0,0.5" transparency="0.25"/>
+            </component>
+        </section_structure><section_properties>
+            <property_component_ref>
+                <property name="diffuse_color" value="#00FF00"/>
+                <property name="shininess" value="0.5"/>
+            </property_component_ref>
+            <property_component_ref/>
+        </section_properties></PV_FILE>"##;
+        let items = create_items(xml, &ConvertOptions::default()).unwrap();
+
+        assert_eq!(None, items[0].material_override);
+        assert_eq!(
+            Some(ColorMaterial {
+                diffuse: core::Color3 { r: 0, g: 255, b: 0 },
+                glossiness: 128,
+                ..ColorMaterial::default()
+            }),
+            items[1].material_override
+        );
+        assert_eq!(
+            Some(ColorMaterial {
+                diffuse: core::Color3 {
+                    r: 255,
+                    g: 0,
+                    b: 128
+                },
+                glossiness: 128,
+                opacity: 191,
+                ..ColorMaterial::default()
+            }),
+            items[2].material_override
+        );
+    }
+
+    #[test]
+    fn transparency_alone_makes_a_white_override() {
+        let xml = r#"<PV_FILE><section_structure>
+            <component name="A"><shape_source file_name="a.ol"/></component>
+            <component name="Root">
+                <component_instance index="0" id="1" transparency="0.5" shininess="0.2"/>
+            </component>
+        </section_structure></PV_FILE>"#;
+        let items = create_items(xml, &ConvertOptions::default()).unwrap();
+
+        assert_eq!(
+            Some(ColorMaterial {
+                diffuse: core::Color3::WHITE,
+                glossiness: 51,
+                opacity: 127,
+                ..ColorMaterial::default()
+            }),
+            items[1].material_override
+        );
+    }
+
+    #[test]
+    fn invalid_material_is_error() {
+        assert_eq!(
+            "Instance '1' in component 'Root' has 2 diffuse_color values but expected 3",
+            error(
+                r#"<PV_FILE><section_structure>
+                    <component name="A"><shape_source file_name="a.ol"/></component>
+                    <component name="Root">
+                        <component_instance index="0" id="1" diffuse_color="1,0"/>
+                    </component>
+                </section_structure></PV_FILE>"#
+            )
+        );
+    }
+
+    #[test]
+    fn snapping_drops_near_identity_transforms() {
+        let xml = r#"<PV_FILE><section_structure>
+            <component name="A"><shape_source file_name="a.ol"/></component>
+            <component name="Root">
+                <component_instance index="0" id="1" translation="0,1e-30,0"/>
+            </component>
+        </section_structure></PV_FILE>"#;
+        let options = ConvertOptions {
+            snap_epsilon: Some(1e-6),
+            ..ConvertOptions::default()
+        };
+
+        assert!(create_items(xml, &ConvertOptions::default()).unwrap()[1]
+            .transform
+            .is_some());
+        assert_eq!(None, create_items(xml, &options).unwrap()[1].transform);
+    }
+
+    const SKEWED_XML: &str = r#"<PV_FILE><section_structure>
+        <component name="A"><shape_source file_name="a.ol"/></component>
+        <component name="Root">
+            <component_instance index="0" id="1" orientation="2,0,0,0.5,1,0,0,0,1"/>
+        </component>
+    </section_structure></PV_FILE>"#;
+
+    #[test]
+    fn checks_orientations() {
+        let ignore = ConvertOptions {
+            orientations: OrientationCheck::Ignore,
+            ..ConvertOptions::default()
+        };
+        let error = ConvertOptions {
+            orientations: OrientationCheck::Error,
+            ..ConvertOptions::default()
+        };
+        let warned = create_items(SKEWED_XML, &ConvertOptions::default()).unwrap();
+
+        assert_eq!(warned, create_items(SKEWED_XML, &ignore).unwrap());
+        assert_eq!(2.0, warned[1].transform.as_ref().unwrap().r0.x);
+        assert_eq!(
+            "Instance '1' in component 'Root' has a non-orthonormal orientation (determinant 2, off by 3)",
+            create_items(SKEWED_XML, &error).unwrap_err().to_string()
+        );
+
+        // Finite entries whose squares overflow must not pass as orthonormal.
+        let overflowing = SKEWED_XML.replace("2,0,0", "1e30,0,0");
+        assert_eq!(
+            "Instance '1' in component 'Root' has a non-orthonormal orientation (determinant 1000000000000000000000000000000, off by inf)",
+            create_items(&overflowing, &error).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn orthonormalizes_orientations() {
+        let options = ConvertOptions {
+            orientations: OrientationCheck::Orthonormalize,
+            ..ConvertOptions::default()
+        };
+        let items = create_items(SKEWED_XML, &options).unwrap();
+
+        // Straightening the skewed columns leaves no rotation at all.
+        assert_eq!(None, items[1].transform);
+    }
+
+    #[test]
+    fn flags_mirrored_items() {
+        let xml = r#"<PV_FILE><section_structure>
+            <component name="A"><shape_source file_name="a.ol"/></component>
+            <component name="B">
+                <component_instance index="0" id="3"/>
+                <component_instance index="0" id="4" orientation="-1,0,0,0,1,0,0,0,1"/>
+            </component>
+            <component name="Root">
+                <component_instance index="1" id="1" orientation="1,0,0,0,-1,0,0,0,1"/>
+                <component_instance index="0" id="2"/>
+            </component>
+        </section_structure></PV_FILE>"#;
+        let options = ConvertOptions {
+            orientations: OrientationCheck::Error,
+            ..ConvertOptions::default()
+        };
+        let items = create_items(xml, &options).unwrap();
+        let mirrored: Vec<(&str, bool)> = items
+            .iter()
+            .map(|i| (i.supplied_id.as_str(), i.mirrored))
+            .collect();
+
+        assert_eq!(
+            vec![
+                ("/", false),
+                ("/1", true),
+                ("/1/3", true),
+                ("/1/4", false),
+                ("/2", false),
+            ],
+            mirrored
+        );
+    }
+
+    #[test]
+    fn double_precision_keeps_millimeters_far_from_origin() {
+        let xml = r#"<PV_FILE><section_structure>
+            <component name="A"><shape_source file_name="a.ol"/></component>
+            <component name="Far"><component_instance index="0" id="2" translation="-100000,0,0"/></component>
+            <component name="Root">
+                <component_instance index="1" id="1" translation="100000.0001,0,0"/>
+            </component>
+        </section_structure></PV_FILE>"#;
+        let double = ConvertOptions {
+            precision: Precision::Double,
+            ..ConvertOptions::default()
+        };
+        let offset = |options: &ConvertOptions| {
+            create_items(xml, options).unwrap()[2]
+                .transform
+                .as_ref()
+                .map_or(0.0, |t| t.r0.w)
+        };
+
+        assert_eq!(0.0, offset(&ConvertOptions::default()));
+        assert!((offset(&double) - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn tolerates_spaced_and_semicolon_separated_values() {
+        let xml = r#"<PV_FILE><section_structure>
+            <component name="A"><shape_source file_name="a.ol" bbox="0; 0; 0; 1e-3; 1E-3; 0.001"/></component>
+            <component name="Root">
+                <component_instance index="0" id="1" translation=" 1, 0, 2e-3 "/>
+            </component>
+        </section_structure></PV_FILE>"#;
+        let items = create_items(xml, &ConvertOptions::default()).unwrap();
+        let transform = items[1].transform.as_ref().unwrap();
+
+        assert_eq!(
+            [1000.0, 0.0, 2.0],
+            [transform.r0.w, transform.r1.w, transform.r2.w]
+        );
+        assert_eq!(
+            Some(BoundingBox3::from_arr_6([
+                1000.0, 0.0, 2.0, 1001.0, 1.0, 3.0
+            ])),
+            items[1].bounding_box
+        );
+    }
 }
@@ -0,0 +1,138 @@
+use crate::{parse_floats, Component, ComponentInstance, PvsError, Scene};
+use core::{Color3, ColorMaterial};
+
+const AMBIENT_COLOR: &str = "ambient_color";
+const DIFFUSE_COLOR: &str = "diffuse_color";
+const EMISSIVE_COLOR: &str = "emissive_color";
+const SHININESS: &str = "shininess";
+const SPECULAR_COLOR: &str = "specular_color";
+const TRANSPARENCY: &str = "transparency";
+
+const ATTRIBUTES: [&str; 6] = [
+    AMBIENT_COLOR,
+    DIFFUSE_COLOR,
+    EMISSIVE_COLOR,
+    SHININESS,
+    SPECULAR_COLOR,
+    TRANSPARENCY,
+];
+
+impl ComponentInstance {
+    fn appearance(&self, attribute: &str) -> Option<&str> {
+        match attribute {
+            AMBIENT_COLOR => self.ambient_color.as_deref(),
+            DIFFUSE_COLOR => self.diffuse_color.as_deref(),
+            EMISSIVE_COLOR => self.emissive_color.as_deref(),
+            SHININESS => self.shininess.as_deref(),
+            SPECULAR_COLOR => self.specular_color.as_deref(),
+            TRANSPARENCY => self.transparency.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+// Appearance of a component, optionally placed by an instance of `parent`. Colors are #RRGGBB
+// or float triplets and shininess and transparency fractions, all between 0 and 1. Instance
+// attributes win over the component's properties of the same name. Any of them makes an
+// override, and values left unset keep the default material's, so an override that only sets
+// transparency or shininess has a white diffuse color.
+pub(crate) fn material(
+    scene: &Scene,
+    component_idx: usize,
+    parent: Option<(&Component, &ComponentInstance)>,
+) -> Result<Option<ColorMaterial>, PvsError> {
+    let appearance = match appearance(scene, component_idx, parent)? {
+        Some(a) => a,
+        None => return Ok(None),
+    };
+
+    let default = ColorMaterial::default();
+    Ok(Some(ColorMaterial {
+        ambient: appearance.ambient.unwrap_or(default.ambient),
+        diffuse: appearance.diffuse.unwrap_or(default.diffuse),
+        emissive: appearance.emissive.unwrap_or(default.emissive),
+        glossiness: appearance.shininess.unwrap_or(default.glossiness),
+        opacity: appearance
+            .transparency
+            .map(|t| u8::MAX - t)
+            .unwrap_or(default.opacity),
+        specular: appearance.specular.unwrap_or(default.specular),
+    }))
+}
+
+// Appearance attributes as parsed, before unset ones fall back to the default material.
+pub(crate) struct Appearance {
+    ambient: Option<Color3>,
+    diffuse: Option<Color3>,
+    emissive: Option<Color3>,
+    shininess: Option<u8>,
+    specular: Option<Color3>,
+    transparency: Option<u8>,
+}
+
+// Parses the appearance without building a material, which is all the measuring pass needs
+// to report invalid values. None when no attribute is set.
+pub(crate) fn appearance(
+    scene: &Scene,
+    component_idx: usize,
+    parent: Option<(&Component, &ComponentInstance)>,
+) -> Result<Option<Appearance>, PvsError> {
+    let lookup = |attribute: &str| {
+        parent
+            .and_then(|(_, instance)| instance.appearance(attribute))
+            .or_else(|| scene.property(component_idx, attribute))
+    };
+    if ATTRIBUTES.iter().all(|a| lookup(a).is_none()) {
+        return Ok(None);
+    }
+
+    let (component, instance_id) = match parent {
+        Some((component, instance)) => (component, instance.id.as_str()),
+        None => (&scene.components[component_idx], ""),
+    };
+    let color = |attribute: &'static str| match lookup(attribute) {
+        Some(v) if v.trim_start().starts_with('#') => {
+            // A hex color is a single token.
+            Color3::from_hex(v)
+                .map(Some)
+                .map_err(|_| PvsError::InvalidNumber {
+                    component: component.name.clone(),
+                    instance_id: instance_id.to_string(),
+                    attribute,
+                    value: v.to_string(),
+                    index: 0,
+                    offset: v.len() - v.trim_start().len(),
+                    token: v.trim().into(),
+                })
+        }
+        Some(v) => parse_floats(
+            v,
+            attribute,
+            core::parse_floats::<3>,
+            component,
+            instance_id,
+        )
+        .map(|c| Some(Color3::from_unit(c))),
+        None => Ok(None),
+    };
+    let fraction = |attribute: &'static str| match lookup(attribute) {
+        Some(v) => parse_floats(
+            v,
+            attribute,
+            core::parse_floats::<1>,
+            component,
+            instance_id,
+        )
+        .map(|[f]| Some(core::unit_to_u8(f))),
+        None => Ok(None),
+    };
+
+    Ok(Some(Appearance {
+        ambient: color(AMBIENT_COLOR)?,
+        diffuse: color(DIFFUSE_COLOR)?,
+        emissive: color(EMISSIVE_COLOR)?,
+        shininess: fraction(SHININESS)?,
+        specular: color(SPECULAR_COLOR)?,
+        transparency: fraction(TRANSPARENCY)?,
+    }))
+}
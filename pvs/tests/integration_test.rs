@@ -1,33 +1,61 @@
-use core::{Transform, Vector4f};
+use core::{BoundingBox3, Transform, Vector3f, Vector4f};
 use pvs::{self, SceneItem, Source};
+use std::collections::BTreeMap;
+
+fn bbox(min: [f32; 3], max: [f32; 3]) -> Option<BoundingBox3> {
+    Some(BoundingBox3::new(
+        Vector3f::new(min[0], min[1], min[2]),
+        Vector3f::new(max[0], max[1], max[2]),
+    ))
+}
+
+fn revision(id: &str) -> BTreeMap<String, String> {
+    let mut metadata = BTreeMap::new();
+    metadata.insert("revId".to_string(), id.to_string());
+    metadata
+}
 
 #[test]
 fn matches_golden_output() {
     let expected = vec![
         SceneItem {
+            bounding_box: bbox([-70.0, -155.0, -15.0], [70.0, 245.0, 70.0]),
             depth: 0,
             material_override: None,
+            metadata: BTreeMap::new(),
+            mirrored: false,
+            name: "TN1, Model".to_string(),
             parent_id: None,
             source: None,
             supplied_id: "/".to_string(),
             transform: None,
+            visible: true,
         },
         SceneItem {
+            bounding_box: bbox([-70.0, -155.0, -15.0], [70.0, 245.0, 70.0]),
             depth: 1,
             material_override: None,
+            metadata: revision("A.2"),
+            mirrored: false,
+            name: "TN1, Model".to_string(),
             parent_id: Some("/".to_string()),
             source: None,
             supplied_id: "/109".to_string(),
             transform: None,
+            visible: true,
         },
         SceneItem {
+            bounding_box: bbox([-70.0, -155.0, -15.0], [70.0, 245.0, 70.0]),
             depth: 2,
             material_override: None,
+            metadata: revision("B"),
+            mirrored: false,
+            name: "PN1, Bolt".to_string(),
             parent_id: Some("/109".to_string()),
             source: Some(Source {
                 file_name: "PN1.ol".to_string(),
                 supplied_part_id: "PN1, Bolt".to_string(),
-                supplied_revision_id: "1".to_string(),
+                supplied_revision_id: "B".to_string(),
             }),
             supplied_id: "/109/104".to_string(),
             transform: Some(Transform {
@@ -56,15 +84,20 @@ fn matches_golden_output() {
                     w: 1.0,
                 },
             }),
+            visible: true,
         },
         SceneItem {
+            bounding_box: bbox([-18.5, 11.5, 45.0], [18.5, 48.5, 48.0]),
             depth: 2,
             material_override: None,
+            metadata: revision("A.1"),
+            mirrored: false,
+            name: "PN0, Washer".to_string(),
             parent_id: Some("/109".to_string()),
             source: Some(Source {
                 file_name: "PN0.ol".to_string(),
                 supplied_part_id: "PN0, Washer".to_string(),
-                supplied_revision_id: "1".to_string(),
+                supplied_revision_id: "A.1".to_string(),
             }),
             supplied_id: "/109/107".to_string(),
             transform: Some(Transform {
@@ -93,15 +126,23 @@ fn matches_golden_output() {
                     w: 1.0,
                 },
             }),
+            visible: true,
         },
         SceneItem {
+            bounding_box: bbox(
+                [-18.5, -3.0, 11.5],
+                [18.5, 0.000000000000000000000000017993725, 48.5],
+            ),
             depth: 1,
             material_override: None,
+            metadata: revision("A.1"),
+            mirrored: false,
+            name: "PN0, Washer".to_string(),
             parent_id: Some("/".to_string()),
             source: Some(Source {
                 file_name: "PN0.ol".to_string(),
                 supplied_part_id: "PN0, Washer".to_string(),
-                supplied_revision_id: "1".to_string(),
+                supplied_revision_id: "A.1".to_string(),
             }),
             supplied_id: "/107".to_string(),
             transform: Some(Transform {
@@ -130,6 +171,7 @@ fn matches_golden_output() {
                     w: 1.0,
                 },
             }),
+            visible: true,
         },
     ];
 
@@ -138,3 +180,86 @@ fn matches_golden_output() {
     assert_eq!(5, items.len());
     assert_eq!(expected, items);
 }
+
+#[test]
+fn streaming_matches_run() {
+    let options = pvs::ConvertOptions::default();
+    let streamed: Vec<SceneItem> = pvs::items("tests/pvs.xml", &options)
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+
+    assert_eq!(pvs::run("tests/pvs.xml").unwrap(), streamed);
+}
+
+#[test]
+fn counts_hidden_instances() {
+    let mut items = pvs::items("tests/pvs.xml", &pvs::ConvertOptions::default()).unwrap();
+    for item in &mut items {
+        item.unwrap();
+    }
+
+    assert_eq!(1, items.hidden_instances());
+}
+
+#[test]
+fn keeps_hidden_instances() {
+    let options = pvs::ConvertOptions {
+        hidden: pvs::HiddenInstances::Keep,
+        ..pvs::ConvertOptions::default()
+    };
+    let mut items = pvs::items("tests/pvs.xml", &options).unwrap();
+    let manual = items
+        .by_ref()
+        .map(Result::unwrap)
+        .find(|i| i.supplied_id == "/102")
+        .unwrap();
+
+    assert_eq!("TN0, Operator's Manual", manual.name);
+    assert!(manual.visible);
+    for item in &mut items {
+        item.unwrap();
+    }
+    assert_eq!(1, items.hidden_instances());
+}
+
+#[test]
+fn repeated_root_name_prefers_unreferenced_component() {
+    let options = pvs::ConvertOptions {
+        root: pvs::RootSelection::Name("TN1, Model".to_string()),
+        ..pvs::ConvertOptions::default()
+    };
+
+    assert_eq!(
+        pvs::run("tests/pvs.xml").unwrap(),
+        pvs::run_with("tests/pvs.xml", &options).unwrap()
+    );
+}
+
+#[test]
+fn snaps_near_zero_translations() {
+    let options = pvs::ConvertOptions {
+        snap_epsilon: Some(1e-6),
+        ..pvs::ConvertOptions::default()
+    };
+    let items = pvs::run_with("tests/pvs.xml", &options).unwrap();
+    let washer = items.iter().find(|i| i.supplied_id == "/107").unwrap();
+    let transform = washer.transform.as_ref().unwrap();
+
+    assert_eq!(0.0, transform.r1.w);
+    assert_eq!(30.0, transform.r2.w);
+    assert_eq!(1.0, transform.r0.x);
+}
+
+#[test]
+fn double_precision_matches_golden_output() {
+    let options = pvs::ConvertOptions {
+        precision: pvs::Precision::Double,
+        ..pvs::ConvertOptions::default()
+    };
+
+    assert_eq!(
+        pvs::run("tests/pvs.xml").unwrap(),
+        pvs::run_with("tests/pvs.xml", &options).unwrap()
+    );
+}